@@ -0,0 +1,84 @@
+//! Announcing a change in the guild
+//!
+//! Guilds can turn this on with /announcements. The embed credits whoever posted the image.
+
+use std::{fmt::Write as _, sync::Arc};
+
+use poise::serenity_prelude::{Context, CreateEmbed, CreateEmbedAuthor, CreateMessage, Message};
+use tracing::debug;
+use url::Url;
+
+use crate::{
+    Error, State,
+    localization::{format, guild_language, target_name, tr},
+    notification::notification_channel,
+    schedule::Schedule,
+    utils::{current_unix_timestamp, next_run},
+};
+
+/// Post an embed with the new image, its source and when the next change happens
+///
+/// Does nothing if the guild has announcements turned off or has no channel to post in.
+/// `media` are the images that were used, more than one for collages
+pub async fn announce_change(
+    ctx: &Context,
+    schedule: &Schedule,
+    media: &[(Url, Message)],
+) -> Result<(), Error> {
+    let guild_id = schedule.guild_id();
+    let Some((first_url, first_message)) = media.first() else {
+        return Ok(());
+    };
+
+    let state: Arc<State> = ctx.data();
+    let settings = state.guild_settings(guild_id).await?;
+    if !settings.announcements() {
+        return Ok(());
+    }
+
+    let channel_id = match settings.announcement_channel() {
+        Some(channel_id) => channel_id,
+        None => match notification_channel(ctx, guild_id).await? {
+            Some(channel_id) => channel_id,
+            None => return Ok(()),
+        },
+    };
+
+    let language = guild_language(ctx, guild_id).await?;
+    let mut sources = String::with_capacity(100 * media.len());
+    for (_, message) in media {
+        let posted_by = format(
+            language,
+            "announcement.posted_by",
+            &[("author", &message.author.name), ("link", &message.link())],
+        );
+        writeln!(sources, "{posted_by}")?;
+    }
+
+    let now = current_unix_timestamp();
+    let next_change = now + next_run(schedule.start_at(), now, schedule.interval());
+
+    let author = CreateEmbedAuthor::new(&*first_message.author.name).icon_url(first_message.author.face());
+    let embed = CreateEmbed::new()
+        .title(format(
+            language,
+            "announcement.title",
+            &[("target", &target_name(language, schedule.target()))],
+        ))
+        .author(author)
+        .description(sources)
+        .image(first_url.as_str())
+        .field(
+            tr(language, "announcement.next_change"),
+            format!("<t:{next_change}:R>"),
+            true,
+        )
+        .colour((255, 0, 255));
+
+    debug!("Announcing change in guild={guild_id} in channel={channel_id}");
+    channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
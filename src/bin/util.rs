@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::SocketAddr,
+};
 
 use clap::Parser;
 use discord_banner_bot::{
@@ -7,10 +10,13 @@ use discord_banner_bot::{
     commands::commands,
     database::Database,
     error::Error,
-    finding_media::{MediaWithMessage, find_media_in_channel},
+    finding_media::{MediaWithMessage, find_media_in_channel, media_source_channels},
+    schedule_target::ScheduleKey,
     utils::{dm_user, start_logging},
+    webhook::receiver,
 };
 use poise::serenity_prelude::{self, GuildId, Http, MessageBuilder, PartialGuild, UserId};
+use tokio::net::TcpListener;
 use tokio_stream::StreamExt;
 use tracing::{error, info};
 
@@ -24,6 +30,15 @@ async fn main() -> Result<(), Error> {
     println!("Using log level: {}", settings.bot.log_level);
 
     start_logging(&settings.bot.log_level);
+
+    // doesn't need discord or the database
+    if let UtilCommand::WebhookReceiver { address } = cli.command {
+        if let Err(err) = webhook_receiver(address).await {
+            error!("Webhook receiver stopped: {err}");
+        }
+        return Ok(());
+    }
+
     let http = serenity_prelude::HttpBuilder::new(settings.bot.token.clone()).build();
     http.set_application_id(http.get_current_application_info().await?.id);
     let database = Database::setup(&settings.database).await?;
@@ -37,14 +52,26 @@ async fn main() -> Result<(), Error> {
             mention_owned_guilds,
         } => dm_server_owners(&http, &database, who, message, mention_owned_guilds).await?,
         UtilCommand::FindMedia { channel_id, limit } => {
-            let mut thingies: Vec<MediaWithMessage> = find_media_in_channel(&http, &channel_id, limit)
-                .filter_map(Result::ok)
-                .collect()
-                .await;
+            let mut thingies: Vec<MediaWithMessage> = Vec::new();
+            for (source, limit) in media_source_channels(&http, channel_id, limit).await? {
+                let media: Vec<MediaWithMessage> = find_media_in_channel(&http, &source, limit, None)
+                    .filter_map(Result::ok)
+                    .collect()
+                    .await;
+                thingies.extend(media);
+            }
             thingies.reverse();
 
             for media in thingies {
-                println!("{}:\n\t{}\n", media.message.link(), media.media);
+                match media.forwarded_from {
+                    Some(original) => println!(
+                        "{} (forwarded from {}):\n\t{}\n",
+                        media.message.link(),
+                        original.link(),
+                        media.media
+                    ),
+                    None => println!("{}:\n\t{}\n", media.message.link(), media.media),
+                }
             }
         }
         UtilCommand::LeaveGuild { guild_id } => {
@@ -53,6 +80,7 @@ async fn main() -> Result<(), Error> {
                 Err(err) => error!("Failed to leave guild {}: {err:#}", guild_id),
             }
         }
+        UtilCommand::WebhookReceiver { .. } => unreachable!("handled before connecting"),
     };
 
     Ok(())
@@ -160,5 +188,45 @@ async fn get_all_owners(http: &Http) -> Result<impl Iterator<Item = GuildId> + u
 async fn get_owners_with_active_schedule(
     database: &Database,
 ) -> Result<impl Iterator<Item = GuildId> + use<>, Error> {
-    Ok(database.active_schedules().await?.into_iter().map(GuildId::new))
+    // a guild with both a banner and an icon schedule is only listed once
+    let guild_ids: BTreeSet<GuildId> = database
+        .active_schedules()
+        .await?
+        .into_iter()
+        .filter_map(|id| id.parse::<ScheduleKey>().ok())
+        .map(|key| key.guild_id)
+        .collect();
+    Ok(guild_ids.into_iter())
+}
+
+/// A stand-in for a real webhook receiver. Prints every event and whether its signature is valid
+async fn webhook_receiver(address: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    info!("Waiting for webhook events on http://{address}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            let delivery = match receiver::receive(stream, &Settings::get().webhook.secret).await {
+                Ok(delivery) => delivery,
+                Err(err) => {
+                    error!("Could not receive webhook from {peer}: {err}");
+                    return;
+                }
+            };
+
+            let valid = delivery.signature_valid;
+            match delivery.event() {
+                Ok(event) => println!(
+                    "{} (signature valid: {valid}):\n\t{event:?}\n",
+                    delivery.request_line
+                ),
+                Err(err) => println!(
+                    "{} (signature valid: {valid}): not an event: {err}\n\t{}\n",
+                    delivery.request_line,
+                    String::from_utf8_lossy(&delivery.body)
+                ),
+            }
+        });
+    }
 }
@@ -0,0 +1,195 @@
+//! Crediting the artist on the banner
+//!
+//! A small caption like "art by @name", in the guild's language, is drawn into a corner of the banner,
+//! on top of a dark box so it stays readable on bright images.
+//! The font is bundled, so this works without any fonts installed.
+
+use std::{fmt::Display, str::FromStr, sync::LazyLock};
+
+use ab_glyph::{Font, FontRef, Glyph, PxScale, ScaleFont, point};
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    Settings,
+    localization::{Language, format},
+};
+
+/// The font every caption is drawn with
+static FONT: LazyLock<FontRef<'static>> = LazyLock::new(|| {
+    FontRef::try_from_slice(include_bytes!("../assets/DejaVuSans.ttf")).expect("bundled font is valid")
+});
+
+/// Color of the text
+const TEXT_COLOR: [u8; 3] = [255, 255, 255];
+
+/// Color of the box behind the text
+const BOX_COLOR: [u8; 3] = [0, 0, 0];
+
+/// How see-through the box is compared to the text
+const BOX_OPACITY: f32 = 0.6;
+
+/// Corner of the banner the caption is drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum CaptionPosition {
+    #[name = "Top left"]
+    TopLeft,
+    #[name = "Top right"]
+    TopRight,
+    #[name = "Bottom left"]
+    BottomLeft,
+    #[name = "Bottom right"]
+    BottomRight,
+}
+
+impl CaptionPosition {
+    /// How the position is stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TopLeft => "top_left",
+            Self::TopRight => "top_right",
+            Self::BottomLeft => "bottom_left",
+            Self::BottomRight => "bottom_right",
+        }
+    }
+}
+
+impl FromStr for CaptionPosition {
+    type Err = CaptionPositionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top_left" => Ok(Self::TopLeft),
+            "top_right" => Ok(Self::TopRight),
+            "bottom_left" => Ok(Self::BottomLeft),
+            "bottom_right" => Ok(Self::BottomRight),
+            other => Err(CaptionPositionParseError(other.to_owned())),
+        }
+    }
+}
+
+impl Display for CaptionPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown caption position: {0}")]
+pub struct CaptionPositionParseError(String);
+
+/// A line of text to draw onto the banner
+#[derive(Debug, Clone)]
+pub struct Caption {
+    pub text: String,
+    pub position: CaptionPosition,
+    /// Height of the text in pixels, on a banner of full size
+    pub font_size: f32,
+    /// 0 is invisible, 1 is fully opaque
+    pub opacity: f32,
+}
+
+impl Caption {
+    /// Credit the authors of the image. Font size and opacity come from the settings
+    pub fn credit(language: Language, authors: &[&str], position: CaptionPosition) -> Self {
+        let settings = &Settings::get().caption;
+        let authors: Vec<String> = authors.iter().map(|author| format!("@{author}")).collect();
+        Self {
+            text: format(language, "caption.credit", &[("authors", &authors.join(", "))]),
+            position,
+            font_size: settings.font_size,
+            opacity: settings.opacity,
+        }
+    }
+
+    /// Draw the caption onto an image
+    ///
+    /// `scale` is how big the image is compared to the full banner size.
+    /// Images smaller than the banner get a smaller caption, so it covers the same part of the image.
+    pub fn draw(&self, image: &mut RgbaImage, scale: f32) {
+        let font_size = (self.font_size * scale).max(1.0);
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        let padding = (font_size / 3.0).ceil() as i64;
+
+        let font = FONT.as_scaled(PxScale::from(font_size));
+        let (glyphs, text_width) = layout(font.scale(), &self.text);
+        let text_height = (font.ascent() - font.descent()).ceil() as i64;
+
+        let box_width = text_width.ceil() as i64 + 2 * padding;
+        let box_height = text_height + 2 * padding;
+        let (image_width, image_height) = (i64::from(image.width()), i64::from(image.height()));
+
+        let box_x = match self.position {
+            CaptionPosition::TopLeft | CaptionPosition::BottomLeft => padding,
+            CaptionPosition::TopRight | CaptionPosition::BottomRight => image_width - box_width - padding,
+        };
+        let box_y = match self.position {
+            CaptionPosition::TopLeft | CaptionPosition::TopRight => padding,
+            CaptionPosition::BottomLeft | CaptionPosition::BottomRight => image_height - box_height - padding,
+        };
+
+        for y in box_y..box_y + box_height {
+            for x in box_x..box_x + box_width {
+                blend(image, x, y, BOX_COLOR, opacity * BOX_OPACITY);
+            }
+        }
+
+        let (text_x, text_y) = (box_x + padding, box_y + padding);
+        for glyph in glyphs {
+            let Some(outline) = FONT.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let x = text_x + bounds.min.x as i64 + i64::from(x);
+                let y = text_y + bounds.min.y as i64 + i64::from(y);
+                blend(image, x, y, TEXT_COLOR, coverage * opacity);
+            });
+        }
+    }
+}
+
+/// Place the glyphs of `text` on a single line. Returns the glyphs and the width of the line
+fn layout(scale: PxScale, text: &str) -> (Vec<Glyph>, f32) {
+    let font = FONT.as_scaled(scale);
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut caret = 0.0;
+    let mut previous = None;
+
+    for character in text.chars().filter(|character| !character.is_control()) {
+        let id = font.glyph_id(character);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(caret, font.ascent())));
+        caret += font.h_advance(id);
+        previous = Some(id);
+    }
+
+    (glyphs, caret)
+}
+
+/// Mix `color` into the pixel at `x`, `y`. Pixels outside of the image are ignored
+fn blend(image: &mut RgbaImage, x: i64, y: i64, color: [u8; 3], alpha: f32) {
+    let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+        return;
+    };
+    if x >= image.width() || y >= image.height() {
+        return;
+    }
+
+    let alpha = alpha.clamp(0.0, 1.0);
+    let Rgba([r, g, b, a]) = *image.get_pixel(x, y);
+    let mix =
+        |below: u8, above: u8| (f32::from(below) * (1.0 - alpha) + f32::from(above) * alpha).round() as u8;
+
+    image.put_pixel(
+        x,
+        y,
+        Rgba([
+            mix(r, color[0]),
+            mix(g, color[1]),
+            mix(b, color[2]),
+            a.max((alpha * 255.0).round() as u8),
+        ]),
+    );
+}
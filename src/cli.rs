@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use clap::{Parser, Subcommand, ValueEnum};
 use poise::serenity_prelude::{GenericChannelId, GuildId};
@@ -46,6 +46,11 @@ pub enum UtilCommand {
         #[arg(short, long)]
         guild_id: GuildId,
     },
+    /// Print webhook events sent to this address and check their signatures
+    WebhookReceiver {
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        address: SocketAddr,
+    },
 }
 
 #[derive(Debug, Clone, Subcommand)]
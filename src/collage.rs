@@ -0,0 +1,105 @@
+//! Tiling several images into one banner
+//!
+//! Handy for screenshot channels, where a single image rarely fills a banner nicely.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::image_pipeline::BannerSize;
+
+/// How the images of a collage are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum CollageLayout {
+    #[name = "Two side by side"]
+    SideBySide,
+    #[name = "Three columns"]
+    Columns,
+    #[name = "One big, two small"]
+    Featured,
+    #[name = "Grid of four"]
+    Grid,
+}
+
+/// Part of the banner a single image is drawn into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CollageLayout {
+    /// How many images the layout needs
+    pub fn image_count(self) -> usize {
+        match self {
+            Self::SideBySide => 2,
+            Self::Columns | Self::Featured => 3,
+            Self::Grid => 4,
+        }
+    }
+
+    /// Where each image goes on a banner of this size. Always [Self::image_count] tiles
+    pub fn tiles(self, size: BannerSize) -> Vec<Tile> {
+        let BannerSize { width, height } = size;
+        let tile = |x, y, right: u32, bottom: u32| Tile {
+            x,
+            y,
+            width: right.saturating_sub(x).max(1),
+            height: bottom.saturating_sub(y).max(1),
+        };
+
+        match self {
+            Self::SideBySide => vec![tile(0, 0, width / 2, height), tile(width / 2, 0, width, height)],
+            Self::Columns => vec![
+                tile(0, 0, width / 3, height),
+                tile(width / 3, 0, width * 2 / 3, height),
+                tile(width * 2 / 3, 0, width, height),
+            ],
+            Self::Featured => vec![
+                tile(0, 0, width * 2 / 3, height),
+                tile(width * 2 / 3, 0, width, height / 2),
+                tile(width * 2 / 3, height / 2, width, height),
+            ],
+            Self::Grid => vec![
+                tile(0, 0, width / 2, height / 2),
+                tile(width / 2, 0, width, height / 2),
+                tile(0, height / 2, width / 2, height),
+                tile(width / 2, height / 2, width, height),
+            ],
+        }
+    }
+
+    /// How the layout is stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SideBySide => "side_by_side",
+            Self::Columns => "columns",
+            Self::Featured => "featured",
+            Self::Grid => "grid",
+        }
+    }
+}
+
+impl FromStr for CollageLayout {
+    type Err = CollageLayoutParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "side_by_side" => Ok(Self::SideBySide),
+            "columns" => Ok(Self::Columns),
+            "featured" => Ok(Self::Featured),
+            "grid" => Ok(Self::Grid),
+            other => Err(CollageLayoutParseError(other.to_owned())),
+        }
+    }
+}
+
+impl Display for CollageLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown collage layout: {0}")]
+pub struct CollageLayoutParseError(String);
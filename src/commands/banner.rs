@@ -2,52 +2,112 @@ use std::num::{NonZeroU32, NonZeroU64};
 
 use chrono::{DateTime, Utc};
 use poise::{
-    CreateReply,
+    ChoiceParameter, CreateReply,
     serenity_prelude::{CreateEmbed, EmbedMessageBuilding, GenericChannelId, GuildId, MessageBuilder},
 };
 use tracing::instrument;
 
 use crate::{
-    Context, Error, Settings, error::Command as CommandErr, finding_media::last_reachable_message,
-    interval::Interval, schedule::ScheduleBuilder, utils::current_unix_timestamp,
+    Context, Error, Settings,
+    caption::CaptionPosition,
+    collage::CollageLayout,
+    error::Command as CommandErr,
+    finding_media::last_reachable_message,
+    interval::Interval,
+    localization::{Language, command_language, format, target_name, tr},
+    media_kind::MediaFilter,
+    schedule::{Schedule, ScheduleBuilder},
+    schedule_target::{ScheduleKey, ScheduleTarget},
+    utils::current_unix_timestamp,
+    webhook::{self, Event, EventKind},
 };
 
-/// Picks a random image from the channel every interval minutes and sets it as the banner.
+/// Picks a random image from the channel every interval minutes and sets it as the banner or icon.
 #[poise::command(
     prefix_command,
     slash_command,
     required_bot_permissions = "MANAGE_GUILD | VIEW_CHANNEL | READ_MESSAGE_HISTORY | SEND_MESSAGES | SEND_MESSAGES_IN_THREADS",
     required_permissions = "MANAGE_GUILD",
     default_member_permissions = "MANAGE_GUILD",
-    guild_only
+    guild_only,
+    description_localized(
+        "de",
+        "Setzt alle paar Minuten ein zufälliges Bild aus dem Kanal als Banner oder Icon."
+    )
 )]
 #[instrument(skip_all)]
 pub async fn start(
     ctx: Context<'_>,
     #[description = "Channel"]
+    #[description_localized("de", "Kanal")]
     #[rename = "channel"]
     channel_id: GenericChannelId,
+    #[description = "Which image to change. Default is the banner."]
+    #[description_localized("de", "Welches Bild wechselt. Standard ist das Banner.")]
+    target: Option<ScheduleTarget>,
     #[description = "After how many minutes the image should change. Default is 30, minimum 15."]
+    #[description_localized(
+        "de",
+        "Nach wie vielen Minuten das Bild wechselt. Standard ist 30, mindestens 15."
+    )]
     #[min = 15]
     #[string]
     interval: Option<Interval>,
     #[description = "When to start the schedule. Default is instantly."]
+    #[description_localized("de", "Wann der Zeitplan startet. Standard ist sofort.")]
     #[string]
     start_at: Option<DateTime<Utc>>,
     #[description = "How many messages to look back for images."]
+    #[description_localized("de", "In wie vielen Nachrichten nach Bildern gesucht wird.")]
     #[min = 0]
     #[max = 300]
     message_limit: Option<u32>,
+    #[description = "Which kind of images to pick. Default is any."]
+    #[description_localized("de", "Welche Art von Bildern gewählt wird. Standard ist alle.")]
+    media_filter: Option<MediaFilter>,
+    #[description = "Only use images from the last n days. Default is no limit."]
+    #[description_localized("de", "Nur Bilder der letzten n Tage. Standard ist keine Grenze.")]
+    #[min = 1]
+    lookback_days: Option<u32>,
+    #[description = "Without animated banners, use this frame of animated images. 1 is the first frame."]
+    #[description_localized(
+        "de",
+        "Ohne animierte Banner wird dieses Bild animierter Bilder benutzt. 1 ist das erste."
+    )]
+    #[min = 1]
+    still_frame: Option<u32>,
+    #[description = "Corner to credit the artist in. Default is none."]
+    #[description_localized("de", "Ecke, in der die Urheberschaft steht. Standard ist keine.")]
+    caption: Option<CaptionPosition>,
+    #[description = "Tile 2 to 4 images into one banner. Default is off."]
+    #[description_localized("de", "Setzt 2 bis 4 Bilder zu einem Banner zusammen. Standard ist aus.")]
+    collage: Option<CollageLayout>,
 ) -> Result<(), Error> {
     let guild_id = ctx.guild_id().ok_or(CommandErr::GuildOnly)?;
     let options = StartBannerOptions::new(Settings::get(), guild_id, channel_id)
+        .target(target)
         .interval(interval)?
         .start_at(start_at)?
-        .message_limit(message_limit)?;
-    start_banner(ctx, options).await
+        .lookback_days(lookback_days)
+        .message_limit(message_limit)
+        .media_filter(media_filter)
+        .still_frame(still_frame)
+        .caption(caption)
+        .collage(collage);
+    start_banner(ctx, options).await?;
+
+    // notifications fall back to this channel, see /notification_channel
+    let state = ctx.data();
+    let settings = state
+        .guild_settings(guild_id)
+        .await?
+        .with_start_channel(ctx.channel_id());
+    state.set_guild_settings(&settings).await?;
+
+    Ok(())
 }
 
-/// Picks a random image from the channel every n minutes and sets it as the banner for that server.
+/// Picks a random image from the channel every n minutes and sets it as the banner or icon for that server.
 #[poise::command(prefix_command, slash_command, hide_in_help, owners_only)]
 #[instrument(skip_all)]
 pub async fn start_for_guild(
@@ -58,6 +118,7 @@ pub async fn start_for_guild(
     #[description = "Channel"]
     #[rename = "channel"]
     channel_id: GenericChannelId,
+    #[description = "Which image to change. Default is the banner."] target: Option<ScheduleTarget>,
     #[description = "After how many minutes the image should change. Default is 30, minimum 15."]
     #[min = 15]
     #[string]
@@ -69,11 +130,26 @@ pub async fn start_for_guild(
     #[min = 0]
     #[max = 300]
     message_limit: Option<u32>,
+    #[description = "Which kind of images to pick. Default is any."] media_filter: Option<MediaFilter>,
+    #[description = "Only use images from the last n days. Default is no limit."]
+    #[min = 1]
+    lookback_days: Option<u32>,
+    #[description = "Without animated banners, use this frame of animated images. 1 is the first frame."]
+    #[min = 1]
+    still_frame: Option<u32>,
+    #[description = "Corner to credit the artist in. Default is none."] caption: Option<CaptionPosition>,
+    #[description = "Tile 2 to 4 images into one banner. Default is off."] collage: Option<CollageLayout>,
 ) -> Result<(), Error> {
     let options = StartBannerOptions::new(Settings::get(), guild_id, channel_id)
+        .target(target)
         .interval(interval)?
         .start_at(start_at)?
-        .message_limit(message_limit)?;
+        .lookback_days(lookback_days)
+        .message_limit(message_limit)
+        .media_filter(media_filter)
+        .still_frame(still_frame)
+        .caption(caption)
+        .collage(collage);
 
     start_banner(ctx, options).await
 }
@@ -84,12 +160,18 @@ pub async fn start_for_guild(
     slash_command,
     required_bot_permissions = "SEND_MESSAGES | SEND_MESSAGES_IN_THREADS",
     required_permissions = "MANAGE_GUILD",
-    default_member_permissions = "MANAGE_GUILD"
+    default_member_permissions = "MANAGE_GUILD",
+    description_localized("de", "Stoppt den Zeitplan")
 )]
 #[instrument(skip_all)]
-pub async fn stop(ctx: Context<'_>) -> Result<(), Error> {
+pub async fn stop(
+    ctx: Context<'_>,
+    #[description = "Which schedule to stop. Default is the banner."]
+    #[description_localized("de", "Welcher Zeitplan gestoppt wird. Standard ist das Banner.")]
+    target: Option<ScheduleTarget>,
+) -> Result<(), Error> {
     let guild_id = ctx.guild_id().ok_or(CommandErr::GuildOnly)?;
-    stop_banner(ctx, guild_id).await
+    stop_banner(ctx, ScheduleKey::new(guild_id, target.unwrap_or_default())).await
 }
 
 /// Stops the schedule in that server
@@ -100,8 +182,9 @@ pub async fn stop_for_guild(
     #[description = "Guild ID"]
     #[rename = "guild"]
     guild_id: GuildId,
+    #[description = "Which schedule to stop. Default is the banner."] target: Option<ScheduleTarget>,
 ) -> Result<(), Error> {
-    stop_banner(ctx, guild_id).await
+    stop_banner(ctx, ScheduleKey::new(guild_id, target.unwrap_or_default())).await
 }
 
 /// Provides information on the currently running schedule
@@ -111,36 +194,58 @@ pub async fn stop_for_guild(
     required_bot_permissions = "SEND_MESSAGES | SEND_MESSAGES_IN_THREADS",
     required_permissions = "MANAGE_GUILD",
     default_member_permissions = "MANAGE_GUILD",
-    guild_only
+    guild_only,
+    description_localized("de", "Zeigt den laufenden Zeitplan")
 )]
 #[instrument(skip_all)]
-pub async fn current_schedule(ctx: Context<'_>) -> Result<(), Error> {
+pub async fn current_schedule(
+    ctx: Context<'_>,
+    #[description = "Which schedule to show. Default is the banner."]
+    #[description_localized("de", "Welcher Zeitplan gezeigt wird. Standard ist das Banner.")]
+    target: Option<ScheduleTarget>,
+) -> Result<(), Error> {
     let guild_id = ctx.guild_id().ok_or(CommandErr::GuildOnly)?;
+    let key = ScheduleKey::new(guild_id, target.unwrap_or_default());
 
     let state = ctx.data();
-    let Some(schedule) = state.get_schedule(guild_id).await? else {
+    let language = command_language(ctx).await?;
+    let Some(schedule) = state.get_schedule(key).await? else {
         // answer the user
         poise::send_reply(
             ctx,
             CreateReply::default()
-                .content("There is no schedule running")
+                .content(tr(language, "schedule.none_running"))
                 .ephemeral(true),
         )
         .await?;
         return Ok(());
     };
 
-    let message_limit = schedule.message_limit().map(NonZeroU32::get).unwrap_or_default();
-
     let message_builder = MessageBuilder::new()
-        .push("Channel: ")
+        .push(tr(language, "schedule.channel"))
         .channel(schedule.channel_id())
-        .push(format!(". Message limit: {message_limit}.",).as_str());
+        .push(schedule_details(&schedule, language).as_str());
+
+    let message_builder = match state.database().pool_stats(key).await? {
+        Some(stats) => message_builder.push(" ").push(&*format(
+            language,
+            "schedule.pool_stats",
+            &[
+                ("candidates", &stats.candidates),
+                ("duplicates", &stats.duplicates),
+            ],
+        )),
+        None => message_builder,
+    };
 
     let message = match last_reachable_message(ctx.http(), &schedule).await {
         Some(msg) => message_builder
-            .push(" Last reachable message: ")
-            .push_named_link("click here", msg.link().to_string().as_str())
+            .push(" ")
+            .push(tr(language, "schedule.last_reachable_message"))
+            .push_named_link(
+                tr(language, "schedule.click_here"),
+                msg.link().to_string().as_str(),
+            )
             .build(),
         None => message_builder.build(),
     };
@@ -155,7 +260,8 @@ pub async fn current_schedule(ctx: Context<'_>) -> Result<(), Error> {
     prefix_command,
     slash_command,
     required_bot_permissions = "SEND_MESSAGES | SEND_MESSAGES_IN_THREADS",
-    guild_only
+    guild_only,
+    description_localized("de", "Link zum Banner, das gerade angezeigt wird")
 )]
 #[instrument(skip_all)]
 pub async fn current_banner(ctx: Context<'_>) -> Result<(), Error> {
@@ -187,12 +293,61 @@ pub async fn current_banner(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Message limit, lookback, media filter, still frame, caption and collage of a schedule
+fn schedule_details(schedule: &Schedule, language: Language) -> String {
+    let message_limit = schedule.message_limit().map(NonZeroU32::get).unwrap_or_default();
+
+    let mut details = vec![format(
+        language,
+        "schedule.message_limit",
+        &[("limit", &message_limit)],
+    )];
+
+    if let Some(days) = schedule.lookback_days() {
+        details.push(format(language, "schedule.lookback", &[("days", &days)]));
+    }
+
+    details.push(format(
+        language,
+        "schedule.media",
+        &[("media", &schedule.media_filter().name())],
+    ));
+
+    if let Some(frame) = schedule.still_frame() {
+        details.push(format(language, "schedule.still_frame", &[("frame", &frame)]));
+    }
+
+    if let Some(position) = schedule.caption() {
+        details.push(format(
+            language,
+            "schedule.caption",
+            &[("caption", &position.name())],
+        ));
+    }
+
+    if let Some(layout) = schedule.collage() {
+        details.push(format(
+            language,
+            "schedule.collage",
+            &[("collage", &layout.name())],
+        ));
+    }
+
+    format!(". {}", details.join(" "))
+}
+
 struct StartBannerOptions {
     guild_id: GuildId,
+    target: ScheduleTarget,
     channel_id: GenericChannelId,
     interval: Interval,
     start_at: Option<DateTime<Utc>>,
-    message_limit: u32,
+    message_limit: Option<u32>,
+    lookback_days: Option<u32>,
+    media_filter: MediaFilter,
+    still_frame: Option<u32>,
+    caption: Option<CaptionPosition>,
+    collage: Option<CollageLayout>,
     settings: &'static Settings,
 }
 
@@ -200,14 +355,26 @@ impl StartBannerOptions {
     pub fn new(settings: &'static Settings, guild_id: GuildId, channel_id: GenericChannelId) -> Self {
         Self {
             guild_id,
+            target: ScheduleTarget::default(),
             channel_id,
             interval: Interval::from_minutes(15).unwrap(),
             start_at: None,
-            message_limit: 200,
+            message_limit: None,
+            lookback_days: None,
+            media_filter: MediaFilter::default(),
+            still_frame: None,
+            caption: None,
+            collage: None,
             settings,
         }
     }
 
+    /// Whether to change the banner or the icon
+    pub fn target(mut self, target: Option<ScheduleTarget>) -> Self {
+        self.target = target.unwrap_or_default();
+        self
+    }
+
     pub fn interval(mut self, interval: Option<Interval>) -> Result<Self, Error> {
         let interval = interval.unwrap_or(self.settings.scheduler.default_interval);
         if interval < self.settings.scheduler.minimum_interval {
@@ -238,25 +405,85 @@ impl StartBannerOptions {
         Ok(self)
     }
 
-    pub fn message_limit(mut self, message_limit: Option<u32>) -> Result<Self, Error> {
-        let message_limit = message_limit.unwrap_or(self.settings.scheduler.default_message_limit);
-        if message_limit > self.settings.scheduler.maximum_message_limit {
+    /// How many messages to look back. Checked by [Self::look_back]
+    pub fn message_limit(mut self, message_limit: Option<u32>) -> Self {
+        self.message_limit = message_limit;
+        self
+    }
+
+    /// Only use images from the last n days. Checked by [Self::look_back]
+    pub fn lookback_days(mut self, lookback_days: Option<u32>) -> Self {
+        self.lookback_days = lookback_days;
+        self
+    }
+
+    /// Check the message limit and lookback days, and fill in the default message limit.
+    ///
+    /// Both are checked together, because the default message limit depends on the lookback
+    pub fn look_back(&self) -> Result<(u32, Option<u32>), Error> {
+        let scheduler = &self.settings.scheduler;
+
+        if let Some(lookback_days) = self.lookback_days {
+            if lookback_days == 0 {
+                return Err(CommandErr::LookbackDaysIsZero.into());
+            }
+
+            if lookback_days > scheduler.maximum_lookback_days {
+                return Err(CommandErr::AboveMaxLookbackDays.into());
+            }
+        }
+
+        let message_limit = match (self.message_limit, self.lookback_days) {
+            (Some(message_limit), _) => message_limit,
+            // the time window decides, the cap is just there to not scan forever
+            (None, Some(_)) => return Ok((scheduler.lookback_message_cap, self.lookback_days)),
+            (None, None) => scheduler.default_message_limit,
+        };
+
+        if message_limit > scheduler.maximum_message_limit {
             return Err(CommandErr::AboveMaxMessageLimit.into());
         }
 
-        self.message_limit = message_limit;
-        Ok(self)
+        Ok((message_limit, self.lookback_days))
+    }
+
+    pub fn media_filter(mut self, media_filter: Option<MediaFilter>) -> Self {
+        self.media_filter = media_filter.unwrap_or_default();
+        self
+    }
+
+    /// Frame to use for animated images when the guild can't have animated banners. Starts at 1
+    pub fn still_frame(mut self, still_frame: Option<u32>) -> Self {
+        self.still_frame = still_frame.filter(|frame| *frame > 0);
+        self
+    }
+
+    /// Where to credit the artist on the banner
+    pub fn caption(mut self, caption: Option<CaptionPosition>) -> Self {
+        self.caption = caption;
+        self
+    }
+
+    /// How to tile several images into one banner
+    pub fn collage(mut self, collage: Option<CollageLayout>) -> Self {
+        self.collage = collage;
+        self
     }
 }
 
 #[instrument(skip_all)]
 async fn start_banner(ctx: Context<'_>, options: StartBannerOptions) -> Result<(), Error> {
+    let (message_limit, lookback_days) = options.look_back()?;
     let StartBannerOptions {
         guild_id,
+        target,
         channel_id,
         interval,
         start_at,
-        message_limit,
+        media_filter,
+        still_frame,
+        caption,
+        collage,
         ..
     } = options;
 
@@ -265,8 +492,30 @@ async fn start_banner(ctx: Context<'_>, options: StartBannerOptions) -> Result<(
     {
         use poise::serenity_prelude::small_fixed_array::FixedString;
         let guild = guild_id.to_partial_guild(ctx.http()).await?;
-        if !guild.features.contains(&FixedString::from_static_trunc("BANNER")) {
-            return Err(CommandErr::GuildHasNoBannerFeature.into());
+        let has = |feature: &'static str| guild.features.contains(&FixedString::from_static_trunc(feature));
+
+        if target.required_feature().is_some_and(|required| !has(required)) {
+            return Err(match target {
+                ScheduleTarget::InviteSplash => CommandErr::GuildHasNoInviteSplashFeature,
+                ScheduleTarget::DiscoverySplash => CommandErr::GuildIsNotDiscoverable,
+                _ => CommandErr::GuildHasNoBannerFeature,
+            }
+            .into());
+        }
+
+        // with a still frame or a collage, animated images can be used as static images
+        if media_filter == MediaFilter::AnimatedOnly
+            && still_frame.is_none()
+            && collage.is_none()
+            && !target.animation().allowed(has)
+        {
+            return Err(match target {
+                ScheduleTarget::Banner => CommandErr::GuildHasNoAnimatedBannerFeature,
+                ScheduleTarget::Icon => CommandErr::GuildHasNoAnimatedIconFeature,
+                // the bot's profile can always be animated, so only splashes end up here
+                _ => CommandErr::SplashCannotBeAnimated,
+            }
+            .into());
         }
     }
 
@@ -283,20 +532,34 @@ async fn start_banner(ctx: Context<'_>, options: StartBannerOptions) -> Result<(
         channel_id,
         NonZeroU64::new(interval.get() * 60).unwrap(),
     )
+    .target(target)
     .message_limit(message_limit)
+    .lookback_days(lookback_days.unwrap_or_default())
+    .media_filter(media_filter)
+    .still_frame(still_frame.unwrap_or_default())
+    .caption(caption)
+    .collage(collage)
     .start_at(start_at);
 
-    state.enque(schedule_builder.build()).await?;
-
-    let content = MessageBuilder::new()
-        .push(&*format!(
-            "Scheduling banner change for every {interval} minutes using channel "
-        ))
-        .channel(channel_id)
-        .push(&*format!(
-            ". Starting at {start_at} in {offset_from_now} seconds."
-        ))
-        .build();
+    let schedule = schedule_builder.build();
+    state.enque(schedule.clone()).await?;
+    webhook::emit(
+        state.reqw_client(),
+        Event::new(EventKind::ScheduleStarted, &schedule),
+    );
+
+    let language = command_language(ctx).await?;
+    let content = format(
+        language,
+        "start.scheduled",
+        &[
+            ("target", &target_name(language, target)),
+            ("interval", &interval),
+            ("channel", &MessageBuilder::new().channel(channel_id).build()),
+            ("start_at", &start_at),
+            ("seconds", &offset_from_now),
+        ],
+    );
 
     // answer the user
     poise::send_reply(ctx, CreateReply::default().content(content).ephemeral(true)).await?;
@@ -305,15 +568,16 @@ async fn start_banner(ctx: Context<'_>, options: StartBannerOptions) -> Result<(
 }
 
 #[instrument(skip_all)]
-async fn stop_banner(ctx: Context<'_>, guild_id: GuildId) -> Result<(), Error> {
+async fn stop_banner(ctx: Context<'_>, key: ScheduleKey) -> Result<(), Error> {
     let state = ctx.data();
+    let language = command_language(ctx).await?;
 
-    let Some(schedule) = state.get_schedule(guild_id).await? else {
+    let Some(schedule) = state.get_schedule(key).await? else {
         // answer the user
         poise::send_reply(
             ctx,
             CreateReply::default()
-                .content("There is no schedule running")
+                .content(tr(language, "schedule.none_running"))
                 .ephemeral(true),
         )
         .await?;
@@ -321,20 +585,26 @@ async fn stop_banner(ctx: Context<'_>, guild_id: GuildId) -> Result<(), Error> {
     };
 
     // unschedule it!
-    state.deque(guild_id).await?;
-
-    let message_limit = schedule.message_limit().map(NonZeroU32::get).unwrap_or_default();
+    state.deque(key).await?;
+    webhook::emit(
+        state.reqw_client(),
+        Event::new(EventKind::ScheduleStopped, &schedule),
+    );
 
     let message_builder = MessageBuilder::new()
-        .push_bold_line("Stopping this schedule:")
-        .push("Channel: ")
+        .push_bold_line(tr(language, "stop.stopping"))
+        .push(tr(language, "schedule.channel"))
         .channel(schedule.channel_id())
-        .push(format!(". Message limit: {message_limit}.",).as_str());
+        .push(schedule_details(&schedule, language).as_str());
 
     let message = match last_reachable_message(ctx.http(), &schedule).await {
         Some(msg) => message_builder
-            .push(" Last reachable message: ")
-            .push_named_link("click here", msg.link().to_string().as_str())
+            .push(" ")
+            .push(tr(language, "schedule.last_reachable_message"))
+            .push_named_link(
+                tr(language, "schedule.click_here"),
+                msg.link().to_string().as_str(),
+            )
             .build(),
         None => message_builder.build(),
     };
@@ -1,13 +1,19 @@
 use tracing::instrument;
 
-use crate::{Context, Error};
+use crate::{
+    Context, Error,
+    localization::{command_language, tr},
+};
 
 /// Display a list of all available commands
-#[poise::command(slash_command, prefix_command)]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    description_localized("de", "Zeigt, wo es Hilfe gibt")
+)]
 #[instrument(skip_all)]
 pub async fn help(ctx: Context<'_>) -> Result<(), Error> {
-    ctx.reply("If you need help, join: https://discord.gg/MMJFtCtYPP and ping 'norom'")
-        .await?;
+    ctx.reply(tr(command_language(ctx).await?, "help.join")).await?;
 
     Ok(())
 }
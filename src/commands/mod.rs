@@ -1,5 +1,6 @@
 pub mod banner;
 pub mod help;
+pub mod settings;
 
 use poise::serenity_prelude::CacheHttp;
 use tracing::instrument;
@@ -84,6 +85,9 @@ pub fn commands() -> Vec<poise::Command<crate::State, crate::Error>> {
         banner::start(),
         banner::stop_for_guild(),
         banner::stop(),
+        settings::notification_channel(),
+        settings::announcements(),
+        settings::language(),
         help::help(),
         register_globally(),
         register(),
@@ -0,0 +1,145 @@
+use poise::{
+    CreateReply,
+    serenity_prelude::{GenericChannelId, MessageBuilder},
+};
+use tracing::instrument;
+
+use crate::{
+    Context, Error,
+    error::Command as CommandErr,
+    localization::{Language, command_language, format, tr},
+    notification,
+};
+
+/// Sets the channel the bot posts problems with the schedule in.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | SEND_MESSAGES_IN_THREADS",
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD",
+    guild_only,
+    description_localized("de", "Legt den Kanal fest, in dem der Bot Probleme mit dem Zeitplan postet.")
+)]
+#[instrument(skip_all)]
+pub async fn notification_channel(
+    ctx: Context<'_>,
+    #[description = "Channel. Default is the system channel, then the channel /start was run in."]
+    #[description_localized("de", "Kanal. Standard ist der Systemkanal, dann der Kanal von /start.")]
+    #[rename = "channel"]
+    channel_id: Option<GenericChannelId>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or(CommandErr::GuildOnly)?;
+
+    let state = ctx.data();
+    let settings = state
+        .guild_settings(guild_id)
+        .await?
+        .with_notification_channel(channel_id);
+    state.set_guild_settings(&settings).await?;
+
+    // without a channel set, show where the fallback ends up
+    let language = command_language(ctx).await?;
+    let content = match notification::notification_channel(ctx.serenity_context(), guild_id).await? {
+        Some(channel_id) => format(
+            language,
+            "notification_channel.set",
+            &[("channel", &MessageBuilder::new().channel(channel_id).build())],
+        ),
+        None => tr(language, "notification_channel.none").to_string(),
+    };
+
+    // answer the user
+    poise::send_reply(ctx, CreateReply::default().content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Turns announcements of every change on or off.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | SEND_MESSAGES_IN_THREADS | EMBED_LINKS",
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD",
+    guild_only,
+    description_localized("de", "Schaltet Ankündigungen für jeden Wechsel an oder aus.")
+)]
+#[instrument(skip_all)]
+pub async fn announcements(
+    ctx: Context<'_>,
+    #[description = "Post the new image, who posted it and when the next change is."]
+    #[description_localized(
+        "de",
+        "Postet das neue Bild, wer es gepostet hat und wann der nächste Wechsel ist."
+    )]
+    enabled: bool,
+    #[description = "Channel. Default is the notification channel."]
+    #[description_localized("de", "Kanal. Standard ist der Kanal für Benachrichtigungen.")]
+    #[rename = "channel"]
+    channel_id: Option<GenericChannelId>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or(CommandErr::GuildOnly)?;
+
+    let state = ctx.data();
+    let settings = state
+        .guild_settings(guild_id)
+        .await?
+        .with_announcements(enabled, channel_id);
+    state.set_guild_settings(&settings).await?;
+
+    let channel_id = match settings.announcement_channel() {
+        Some(channel_id) => Some(channel_id),
+        None => notification::notification_channel(ctx.serenity_context(), guild_id).await?,
+    };
+
+    let language = command_language(ctx).await?;
+    let content = match (enabled, channel_id) {
+        (false, _) => tr(language, "announcements.off").to_string(),
+        (true, Some(channel_id)) => format(
+            language,
+            "announcements.on",
+            &[("channel", &MessageBuilder::new().channel(channel_id).build())],
+        ),
+        (true, None) => tr(language, "announcements.no_channel").to_string(),
+    };
+
+    // answer the user
+    poise::send_reply(ctx, CreateReply::default().content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Sets the language the bot speaks in this server.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | SEND_MESSAGES_IN_THREADS",
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD",
+    guild_only,
+    description_localized("de", "Legt die Sprache fest, die der Bot auf diesem Server spricht.")
+)]
+#[instrument(skip_all)]
+pub async fn language(
+    ctx: Context<'_>,
+    #[description = "Language. Default is the language of each user."]
+    #[description_localized("de", "Sprache. Standard ist die Sprache der jeweiligen Person.")]
+    language: Option<Language>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or(CommandErr::GuildOnly)?;
+
+    let state = ctx.data();
+    let settings = state.guild_settings(guild_id).await?.with_language(language);
+    state.set_guild_settings(&settings).await?;
+
+    let content = match language {
+        Some(language) => tr(language, "language.set"),
+        None => tr(command_language(ctx).await?, "language.reset"),
+    };
+
+    // answer the user
+    poise::send_reply(ctx, CreateReply::default().content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
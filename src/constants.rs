@@ -10,5 +10,27 @@ pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_REPOSITORY"), " - ", env!("
 /// Maximum image size in bytes for uploads to discord
 pub const MAXIMUM_IMAGE_SIZE: usize = 1024 * 1024 * 10; // 10mb
 
+/// Width and height of server icons. Discord shows them much smaller, but keeps the upload
+pub const ICON_SIZE: u32 = 512;
+
+/// Width of invite and discovery splash images
+pub const SPLASH_WIDTH: u32 = 1920;
+
+/// Height of invite and discovery splash images
+pub const SPLASH_HEIGHT: u32 = 1080;
+
+/// Width of the bot's profile banner in a guild
+pub const PROFILE_BANNER_WIDTH: u32 = 600;
+
+/// Height of the bot's profile banner in a guild
+pub const PROFILE_BANNER_HEIGHT: u32 = 240;
+
+/// Maximum image size in bytes for downloads.
+/// Bigger than [MAXIMUM_IMAGE_SIZE] because images are shrunk before uploading
+pub const MAXIMUM_DOWNLOAD_SIZE: usize = 1024 * 1024 * 50; // 50mb
+
+/// First second of 2015 in unix milliseconds. Discord ids count from here
+pub const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
 /// Maximum message length for discord
 pub const DISCORD_MESSAGE_CONTENT_LIMIT: usize = serenity_prelude::constants::MESSAGE_CODE_LIMIT;
@@ -0,0 +1,157 @@
+//! Telling the bot which part of an image matters
+//!
+//! Cropping around the center often cuts off faces.
+//! Posters can add a line like `crop: top` or `focus: 0.3,0.2` to their message,
+//! or they or a moderator can react with an arrow to pick a side.
+//!
+//! The hint is stored in the media index, see [crate::indexing]. Checking who reacted needs requests to discord,
+//! so that only happens when a message is backfilled or its arrow reactions change, never when picking a banner.
+
+use poise::serenity_prelude::{Error as SerenityError, GuildId, Http, Message, PartialGuild, ReactionType};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// How many members who reacted with the same arrow are checked for being a moderator.
+/// Each one is a request
+const MAXIMUM_CHECKED_REACTORS: usize = 10;
+
+/// Which part of an image to keep when cropping it to the banner
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CropHint {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    /// Point to keep in view. `0.0, 0.0` is the top left corner, `1.0, 1.0` the bottom right
+    Focus {
+        x: f32,
+        y: f32,
+    },
+}
+
+impl CropHint {
+    /// The hint of a message. A hint in the text wins over reactions
+    ///
+    /// Only arrows from the poster or from members who can manage messages count.
+    /// Without a `guild_id` only the poster's arrows count.
+    /// Checking who reacted needs requests to discord, if those fail the reaction is ignored
+    pub async fn from_message(http: &Http, guild_id: Option<GuildId>, message: &Message) -> Option<Self> {
+        if let Some(hint) = Self::from_content(&message.content) {
+            return Some(hint);
+        }
+
+        // fetched once, and only if someone other than the poster reacted
+        let mut guild = None;
+        for reaction in &message.reactions {
+            let Some(hint) = Self::from_reaction(&reaction.reaction_type) else {
+                continue;
+            };
+
+            match reacted_by_poster_or_moderator(http, guild_id, &mut guild, message, &reaction.reaction_type)
+                .await
+            {
+                Ok(true) => return Some(hint),
+                Ok(false) => {}
+                Err(err) => warn!("Could not check who reacted to message={}: {err}", message.id),
+            }
+        }
+
+        None
+    }
+
+    /// Look for a line like `crop: top` or `focus: 0.3,0.2`. The first valid one is used
+    pub fn from_content(content: &str) -> Option<Self> {
+        content.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim().to_lowercase();
+
+            match key.trim().to_lowercase().as_str() {
+                "crop" => match value.as_str() {
+                    "center" | "centre" => Some(Self::Center),
+                    "top" => Some(Self::Top),
+                    "bottom" => Some(Self::Bottom),
+                    "left" => Some(Self::Left),
+                    "right" => Some(Self::Right),
+                    _ => None,
+                },
+                "focus" => {
+                    let (x, y) = value.split_once(',')?;
+                    let (x, y) = (x.trim().parse::<f32>().ok()?, y.trim().parse::<f32>().ok()?);
+                    let valid = |value: f32| (0.0..=1.0).contains(&value);
+                    (valid(x) && valid(y)).then_some(Self::Focus { x, y })
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Arrow reactions are presets for the sides of an image
+    pub fn from_reaction(reaction: &ReactionType) -> Option<Self> {
+        let ReactionType::Unicode(emoji) = reaction else {
+            return None;
+        };
+
+        // some clients send the arrows with an emoji variation selector, some without
+        match emoji.trim_end_matches('\u{fe0f}') {
+            "⬆" => Some(Self::Top),
+            "⬇" => Some(Self::Bottom),
+            "⬅" => Some(Self::Left),
+            "➡" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    /// The point to keep in view, as fractions of the width and height
+    pub fn focus(self) -> (f32, f32) {
+        match self {
+            Self::Center => (0.5, 0.5),
+            Self::Top => (0.5, 0.0),
+            Self::Bottom => (0.5, 1.0),
+            Self::Left => (0.0, 0.5),
+            Self::Right => (1.0, 0.5),
+            Self::Focus { x, y } => (x, y),
+        }
+    }
+}
+
+/// Did the poster of the message, or a member who can manage messages, react with this?
+async fn reacted_by_poster_or_moderator(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    guild: &mut Option<PartialGuild>,
+    message: &Message,
+    reaction: &ReactionType,
+) -> Result<bool, SerenityError> {
+    let users = message
+        .reaction_users(http, reaction.clone(), Some(100), None)
+        .await?;
+
+    if users.iter().any(|user| user.id == message.author.id) {
+        return Ok(true);
+    }
+
+    let Some(guild_id) = guild_id else {
+        return Ok(false);
+    };
+
+    for user in users
+        .iter()
+        .filter(|user| !user.bot())
+        .take(MAXIMUM_CHECKED_REACTORS)
+    {
+        let guild = match guild {
+            Some(guild) => guild,
+            None => guild.insert(guild_id.to_partial_guild(http).await?),
+        };
+        let member = guild_id.member(http, user.id).await?;
+
+        if guild.member_permissions(&member).manage_messages() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,54 @@
+//! Images that could not be decoded
+
+use std::collections::HashSet;
+
+use fred::{error::Error, interfaces::KeysInterface, types::Expiration};
+
+use super::Database;
+use crate::Settings;
+
+impl Database {
+    /// Marks an image as corrupt until it expires. See [crate::deduplication::media_identity]
+    fn corrupt_image_key(&self, identity: &str) -> String {
+        self.key(format!("corrupt_images:{identity}"))
+    }
+
+    /// Which of these images recently failed to decode
+    pub async fn corrupt_images(&self, identities: &[String]) -> Result<HashSet<String>, Error> {
+        // MGET without keys is an error
+        if identities.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let keys: Vec<String> = identities
+            .iter()
+            .map(|identity| self.corrupt_image_key(identity))
+            .collect();
+        let mut marks: Vec<Option<String>> = self.client.mget(keys).await?;
+        marks.resize(identities.len(), None);
+
+        Ok(identities
+            .iter()
+            .zip(marks)
+            .filter(|(_, mark)| mark.is_some())
+            .map(|(identity, _)| identity.clone())
+            .collect())
+    }
+
+    /// Remember that an image failed to decode, so it is not picked again for a while.
+    /// A download cut off by the network looks the same, so the mark expires
+    pub async fn mark_image_as_corrupt(&self, identity: &str) -> Result<(), Error> {
+        let days = Settings::get().image.corrupt_image_days.max(1);
+        let seconds = i64::try_from(days * 24 * 60 * 60).unwrap_or(i64::MAX);
+
+        self.client
+            .set(
+                self.corrupt_image_key(identity),
+                1,
+                Some(Expiration::EX(seconds)),
+                None,
+                false,
+            )
+            .await
+    }
+}
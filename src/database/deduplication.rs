@@ -0,0 +1,97 @@
+//! Cached image hashes and how many duplicates a schedule ran into
+
+use std::collections::{HashMap, HashSet};
+
+use fred::{
+    error::Error,
+    interfaces::{HashesInterface, KeysInterface},
+};
+
+use super::Database;
+use crate::{
+    deduplication::{ImageHashes, PoolStats},
+    schedule_target::ScheduleKey,
+};
+
+impl Database {
+    /// Hash of image identities to json encoded [ImageHashes], for the images indexed in a channel
+    fn image_hashes_key(&self, channel_id: u64) -> String {
+        self.key(format!("image_hashes:{channel_id}"))
+    }
+
+    /// Cached hashes of these images in a channel, in the same order.
+    /// See [crate::deduplication::media_identity]
+    pub async fn image_hashes(
+        &self,
+        channel_id: u64,
+        identities: &[String],
+    ) -> Result<Vec<Option<ImageHashes>>, Error> {
+        // HMGET without fields is an error
+        if identities.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut values: Vec<Option<String>> = self
+            .client
+            .hmget(self.image_hashes_key(channel_id), identities.to_vec())
+            .await?;
+        values.resize(identities.len(), None);
+
+        Ok(values
+            .into_iter()
+            .map(|value| value.and_then(|value| serde_json::from_str(&value).ok()))
+            .collect())
+    }
+
+    /// Remember the hashes of an image in a channel
+    pub async fn cache_image_hashes(
+        &self,
+        channel_id: u64,
+        identity: &str,
+        hashes: &ImageHashes,
+    ) -> Result<(), Error> {
+        let value = serde_json::to_string(hashes)
+            .map_err(|err| Error::new(fred::error::ErrorKind::Parse, err.to_string()))?;
+
+        let mut map = HashMap::with_capacity(1);
+        map.insert(identity.to_string(), value);
+        self.client.hset(self.image_hashes_key(channel_id), map).await
+    }
+
+    /// Forget the hashes of every image in a channel that is not in `keep`
+    pub async fn prune_image_hashes(&self, channel_id: u64, keep: &HashSet<String>) -> Result<(), Error> {
+        let identities: Vec<String> = self.client.hkeys(self.image_hashes_key(channel_id)).await?;
+        let stale: Vec<String> = identities
+            .into_iter()
+            .filter(|identity| !keep.contains(identity))
+            .collect();
+
+        // HDEL without fields is an error
+        if stale.is_empty() {
+            return Ok(());
+        }
+
+        self.client.hdel(self.image_hashes_key(channel_id), stale).await
+    }
+
+    /// Forget the hashes of every image in a channel
+    pub async fn drop_image_hashes(&self, channel_id: u64) -> Result<(), Error> {
+        self.client.del(self.image_hashes_key(channel_id)).await
+    }
+
+    /// Stats of the last run of a schedule
+    pub async fn pool_stats(&self, key: ScheduleKey) -> Result<Option<PoolStats>, Error> {
+        let value: Option<String> = self.client.hget(self.key("pool_stats"), key.to_string()).await?;
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    /// Update the stats of a schedule
+    pub async fn set_pool_stats(&self, key: ScheduleKey, stats: PoolStats) -> Result<(), Error> {
+        let value = serde_json::to_string(&stats)
+            .map_err(|err| Error::new(fred::error::ErrorKind::Parse, err.to_string()))?;
+
+        let mut map = HashMap::with_capacity(1);
+        map.insert(key.to_string(), value);
+        self.client.hset(self.key("pool_stats"), map).await
+    }
+}
@@ -8,16 +8,26 @@ use fred::{
     interfaces::{HashesInterface, KeysInterface, SetsInterface},
     types::{FromValue, Key, Map, Value},
 };
+use poise::serenity_prelude::GuildId;
 use tracing::debug;
 
-use super::{Database, Entry, get_from_redis_map};
-use crate::{schedule::Schedule, utils::current_unix_timestamp};
+use super::{Database, Entry, get_from_redis_map, get_optional_from_redis_map};
+use crate::{
+    caption::{CaptionPosition, CaptionPositionParseError},
+    collage::{CollageLayout, CollageLayoutParseError},
+    media_kind::{MediaFilter, MediaFilterParseError},
+    schedule::Schedule,
+    schedule_target::{ScheduleKey, ScheduleTarget, ScheduleTargetParseError},
+    utils::current_unix_timestamp,
+};
 
 /// How a schedule is stored in the database
 #[derive(Debug, Clone, Copy)]
 pub struct GuildSchedule {
     /// The Guilds ID
     guild_id: u64,
+    /// What the schedule changes
+    target: ScheduleTarget,
     /// Channel ID to fetch images from
     channel_id: u64,
     /// How frequent the schudle run. In seconds
@@ -28,24 +38,47 @@ pub struct GuildSchedule {
     last_run: u64,
     /// How many messages to look into the past for
     message_limit: u64,
+    /// How many days to look into the past for. 0 means no limit
+    lookback_days: u64,
+    /// Which kinds of media may be picked
+    media_filter: MediaFilter,
+    /// Which frame of animated images to use on guilds without animated banners. 0 means none
+    still_frame: u64,
+    /// Where to credit the artist on the banner
+    caption: Option<CaptionPosition>,
+    /// How to tile several images into one banner
+    collage: Option<CollageLayout>,
 }
 
 impl GuildSchedule {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         guild_id: u64,
+        target: ScheduleTarget,
         channel_id: u64,
         interval: NonZeroU64,
         last_run: u64,
         start_at: u64,
         message_limit: u64,
+        lookback_days: u64,
+        media_filter: MediaFilter,
+        still_frame: u64,
+        caption: Option<CaptionPosition>,
+        collage: Option<CollageLayout>,
     ) -> Self {
         Self {
             guild_id,
+            target,
             channel_id,
             interval,
             start_at,
             last_run,
             message_limit,
+            lookback_days,
+            media_filter,
+            still_frame,
+            caption,
+            collage,
         }
     }
 
@@ -54,6 +87,16 @@ impl GuildSchedule {
         self.guild_id
     }
 
+    /// Get the db entry's target.
+    pub fn target(&self) -> ScheduleTarget {
+        self.target
+    }
+
+    /// Get the db entry's key. Its string form is the id in the database
+    pub fn key(&self) -> ScheduleKey {
+        ScheduleKey::new(GuildId::new(self.guild_id), self.target)
+    }
+
     /// Get the db entry's channel id.
     pub fn channel_id(&self) -> u64 {
         self.channel_id
@@ -78,11 +121,37 @@ impl GuildSchedule {
     pub fn message_limit(&self) -> u64 {
         self.message_limit
     }
+
+    /// Get the db entry's lookback in days.
+    pub fn lookback_days(&self) -> u64 {
+        self.lookback_days
+    }
+
+    /// Get the db entry's media filter.
+    pub fn media_filter(&self) -> MediaFilter {
+        self.media_filter
+    }
+
+    /// Get the db entry's still frame.
+    pub fn still_frame(&self) -> u64 {
+        self.still_frame
+    }
+
+    /// Get the db entry's caption position.
+    pub fn caption(&self) -> Option<CaptionPosition> {
+        self.caption
+    }
+
+    /// Get the db entry's collage layout.
+    pub fn collage(&self) -> Option<CollageLayout> {
+        self.collage
+    }
 }
 
 impl From<Schedule> for GuildSchedule {
     fn from(schedule: Schedule) -> Self {
         let guild_id = schedule.guild_id().get();
+        let target = schedule.target();
         let channel_id = schedule.channel_id().get();
         let interval = schedule.interval();
         let start_at = schedule.start_at();
@@ -98,13 +167,33 @@ impl From<Schedule> for GuildSchedule {
             .try_into()
             .expect("If the limit does not fit in  a 64 bit uint may god help us all");
 
+        let lookback_days = schedule
+            .lookback_days()
+            .map(NonZeroU32::get)
+            .map(u64::from)
+            .unwrap_or_default();
+        let media_filter = schedule.media_filter();
+        let still_frame = schedule
+            .still_frame()
+            .map(NonZeroU32::get)
+            .map(u64::from)
+            .unwrap_or_default();
+        let caption = schedule.caption();
+        let collage = schedule.collage();
+
         Self {
             guild_id,
+            target,
             channel_id,
             interval,
             start_at,
             last_run,
             message_limit,
+            lookback_days,
+            media_filter,
+            still_frame,
+            caption,
+            collage,
         }
     }
 }
@@ -117,13 +206,25 @@ impl From<GuildSchedule> for Map {
 
 impl From<&GuildSchedule> for Map {
     fn from(entry: &GuildSchedule) -> Self {
-        let mut map = HashMap::with_capacity(6);
+        let mut map = HashMap::with_capacity(12);
         map.insert("guild_id", entry.guild_id.to_string());
+        map.insert("target", entry.target.to_string());
         map.insert("channel_id", entry.channel_id.to_string());
         map.insert("interval", entry.interval.to_string());
         map.insert("last_run", entry.last_run.to_string());
         map.insert("start_at", entry.start_at.to_string());
         map.insert("message_limit", entry.message_limit.to_string());
+        map.insert("lookback_days", entry.lookback_days.to_string());
+        map.insert("media_filter", entry.media_filter.to_string());
+        map.insert("still_frame", entry.still_frame.to_string());
+        map.insert(
+            "caption",
+            entry.caption.map_or("none", CaptionPosition::as_str).to_string(),
+        );
+        map.insert(
+            "collage",
+            entry.collage.map_or("none", CollageLayout::as_str).to_string(),
+        );
 
         // this cannot fail
         Map::try_from(map).unwrap()
@@ -132,7 +233,7 @@ impl From<&GuildSchedule> for Map {
 
 impl From<GuildSchedule> for Key {
     fn from(schedule: GuildSchedule) -> Self {
-        schedule.guild_id.into()
+        schedule.key().to_string().into()
     }
 }
 
@@ -141,6 +242,12 @@ impl FromValue for GuildSchedule {
         let value = value.into_map()?;
 
         let guild_id = get_from_redis_map(&value, "guild_id")?;
+        // schedules from before icons were a thing are all banner schedules
+        let target = get_optional_from_redis_map::<String>(&value, "target")?
+            .map(|target| target.parse())
+            .transpose()
+            .map_err(|err: ScheduleTargetParseError| Error::new(ErrorKind::Parse, err.to_string()))?
+            .unwrap_or_default();
         let channel_id = get_from_redis_map(&value, "channel_id")?;
         let interval = get_from_redis_map::<u64>(&value, "interval")?
             .try_into()
@@ -148,14 +255,37 @@ impl FromValue for GuildSchedule {
         let last_run = get_from_redis_map(&value, "last_run")?;
         let start_at = get_from_redis_map(&value, "start_at")?;
         let message_limit = get_from_redis_map(&value, "message_limit")?;
+        let lookback_days = get_optional_from_redis_map(&value, "lookback_days")?.unwrap_or_default();
+        let media_filter = get_optional_from_redis_map::<String>(&value, "media_filter")?
+            .map(|filter| filter.parse())
+            .transpose()
+            .map_err(|err: MediaFilterParseError| Error::new(ErrorKind::Parse, err.to_string()))?
+            .unwrap_or_default();
+        let still_frame = get_optional_from_redis_map(&value, "still_frame")?.unwrap_or_default();
+        let caption = get_optional_from_redis_map::<String>(&value, "caption")?
+            .filter(|caption| caption != "none")
+            .map(|caption| caption.parse())
+            .transpose()
+            .map_err(|err: CaptionPositionParseError| Error::new(ErrorKind::Parse, err.to_string()))?;
+        let collage = get_optional_from_redis_map::<String>(&value, "collage")?
+            .filter(|collage| collage != "none")
+            .map(|collage| collage.parse())
+            .transpose()
+            .map_err(|err: CollageLayoutParseError| Error::new(ErrorKind::Parse, err.to_string()))?;
 
         Ok(Self {
             guild_id,
+            target,
             channel_id,
             interval,
             start_at,
             last_run,
             message_limit,
+            lookback_days,
+            media_filter,
+            still_frame,
+            caption,
+            collage,
         })
     }
 }
@@ -196,7 +326,7 @@ impl Entry for GuildSchedule {
             Ok(None) => {
                 return Err(Error::new(
                     ErrorKind::NotFound,
-                    "schedule with guild id does not exist",
+                    "schedule with this key does not exist",
                 ));
             }
             Err(err) => return Err(err),
@@ -1,15 +1,105 @@
 use std::collections::HashMap;
 
-use fred::types::Map;
+use fred::{
+    error::{Error, ErrorKind},
+    interfaces::{HashesInterface, KeysInterface},
+    types::{FromValue, Key, Map, Value},
+};
+use poise::serenity_prelude::{GenericChannelId, GuildId};
+
+use super::{Database, Entry, get_from_redis_map, get_optional_from_redis_map};
+use crate::localization::{Language, LanguageParseError};
 
 #[derive(Debug, Clone)]
 pub struct GuildSettings {
     /// The guild id
     guild_id: u64,
-    /// The channel the bot will post messages to.
+    /// The channel the bot will post messages to. 0 means not set.
     /// Will default to Guild `system_channel_id` if available.
     /// Otherwise will use the channel from which the /start was last run
     notification_channel: u64,
+    /// The channel /start was last run in. 0 means unknown
+    start_channel: u64,
+    /// Post an announcement after every change
+    announcements: bool,
+    /// The channel announcements are posted in. 0 means the notification channel
+    announcement_channel: u64,
+    /// Language the bot speaks in this guild. None means the language of each user,
+    /// and the server's community language for notifications
+    language: Option<Language>,
+}
+
+impl GuildSettings {
+    /// Settings of a guild that never changed any
+    pub fn new(guild_id: GuildId) -> Self {
+        Self {
+            guild_id: guild_id.get(),
+            notification_channel: 0,
+            start_channel: 0,
+            announcements: false,
+            announcement_channel: 0,
+            language: None,
+        }
+    }
+
+    /// Get the guild id
+    pub fn guild_id(&self) -> GuildId {
+        GuildId::new(self.guild_id)
+    }
+
+    /// The channel set with /notification_channel, if any. See [Self::start_channel] for the fallback
+    pub fn notification_channel(&self) -> Option<GenericChannelId> {
+        (self.notification_channel != 0).then(|| GenericChannelId::new(self.notification_channel))
+    }
+
+    /// The channel /start was last run in
+    pub fn start_channel(&self) -> Option<GenericChannelId> {
+        (self.start_channel != 0).then(|| GenericChannelId::new(self.start_channel))
+    }
+
+    /// Should every change be announced?
+    pub fn announcements(&self) -> bool {
+        self.announcements
+    }
+
+    /// The channel set for announcements, if any. Otherwise they go to the notification channel
+    pub fn announcement_channel(&self) -> Option<GenericChannelId> {
+        (self.announcement_channel != 0).then(|| GenericChannelId::new(self.announcement_channel))
+    }
+
+    /// The language set with /language, if any
+    pub fn language(&self) -> Option<Language> {
+        self.language
+    }
+
+    /// Set or reset the notification channel
+    #[must_use]
+    pub fn with_notification_channel(mut self, channel_id: Option<GenericChannelId>) -> Self {
+        self.notification_channel = channel_id.map(GenericChannelId::get).unwrap_or_default();
+        self
+    }
+
+    /// Turn announcements on or off, and set or reset their channel
+    #[must_use]
+    pub fn with_announcements(mut self, enabled: bool, channel_id: Option<GenericChannelId>) -> Self {
+        self.announcements = enabled;
+        self.announcement_channel = channel_id.map(GenericChannelId::get).unwrap_or_default();
+        self
+    }
+
+    /// Set or reset the language
+    #[must_use]
+    pub fn with_language(mut self, language: Option<Language>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Remember where /start was run
+    #[must_use]
+    pub fn with_start_channel(mut self, channel_id: GenericChannelId) -> Self {
+        self.start_channel = channel_id.get();
+        self
+    }
 }
 
 impl From<GuildSettings> for Map {
@@ -20,10 +110,83 @@ impl From<GuildSettings> for Map {
 
 impl From<&GuildSettings> for Map {
     fn from(entry: &GuildSettings) -> Self {
-        let mut map = HashMap::with_capacity(5);
+        let mut map = HashMap::with_capacity(6);
         map.insert("guild_id", entry.guild_id.to_string());
         map.insert("notification_channel", entry.notification_channel.to_string());
+        map.insert("start_channel", entry.start_channel.to_string());
+        map.insert("announcements", entry.announcements.to_string());
+        map.insert("announcement_channel", entry.announcement_channel.to_string());
+        map.insert(
+            "language",
+            entry.language.map_or("none", Language::as_str).to_string(),
+        );
 
         Map::try_from(map).unwrap()
     }
 }
+
+impl FromValue for GuildSettings {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        let value = value.into_map()?;
+
+        let guild_id = get_from_redis_map(&value, "guild_id")?;
+        let notification_channel =
+            get_optional_from_redis_map(&value, "notification_channel")?.unwrap_or_default();
+        let start_channel = get_optional_from_redis_map(&value, "start_channel")?.unwrap_or_default();
+        let announcements = get_optional_from_redis_map::<String>(&value, "announcements")?
+            .is_some_and(|enabled| enabled == "true");
+        let announcement_channel =
+            get_optional_from_redis_map(&value, "announcement_channel")?.unwrap_or_default();
+        let language = get_optional_from_redis_map::<String>(&value, "language")?
+            .filter(|language| language != "none")
+            .map(|language| language.parse())
+            .transpose()
+            .map_err(|err: LanguageParseError| Error::new(ErrorKind::Parse, err.to_string()))?;
+
+        Ok(Self {
+            guild_id,
+            notification_channel,
+            start_channel,
+            announcements,
+            announcement_channel,
+            language,
+        })
+    }
+}
+
+impl Entry for GuildSettings {
+    async fn insert(&self, db: &Database, id: impl Into<Key> + Send + Sync) -> Result<(), Error> {
+        db.client.hset(Self::key(db, id), self).await
+    }
+
+    async fn get(db: &Database, id: impl Into<Key> + Send + Sync) -> Result<Option<Self>, Error> {
+        let key = Self::key(db, id);
+
+        // a missing hash reads as an empty one, which is not valid settings
+        let exists: u64 = db.client.exists(key.as_str()).await?;
+        if exists == 0 {
+            return Ok(None);
+        }
+
+        db.client.hgetall(key).await.map(Some)
+    }
+
+    async fn delete(db: &Database, id: impl Into<Key> + Send + Sync) -> Result<Self, Error> {
+        let id: Key = id.into();
+
+        let Some(settings) = Self::get(db, id.clone()).await? else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "settings for guild id do not exist",
+            ));
+        };
+
+        let _: () = db.client.del(Self::key(db, id)).await?;
+
+        Ok(settings)
+    }
+
+    fn namespace() -> &'static str {
+        "guild_settings"
+    }
+}
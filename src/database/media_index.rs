@@ -0,0 +1,280 @@
+//! Index of the media in a source channel
+//!
+//! Scanning the message history on every run is slow and eats into the rate limit.
+//! Instead every channel is scanned once and then kept up to date by gateway events.
+
+use std::collections::HashMap;
+
+use fred::{
+    error::Error,
+    interfaces::{HashesInterface, KeysInterface},
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::Database;
+use crate::{crop_hint::CropHint, finding_media::MediaWithMessage, media_kind::MediaKind};
+
+/// How a single media is stored in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedMedia {
+    /// Url at the time of indexing.
+    /// Discord attachment urls expire, so the message is fetched again before using it
+    pub url: String,
+    /// Channel the message is in
+    pub channel_id: u64,
+    /// Message containing the media
+    pub message_id: u64,
+    /// Which media of the message it is, see [crate::finding_media::media_in_message]
+    pub nth: usize,
+    /// Who posted the message
+    pub author_id: u64,
+    /// Width in pixels, if known
+    pub width: Option<u32>,
+    /// Height in pixels, if known
+    pub height: Option<u32>,
+    /// Static or animated, if known
+    pub kind: Option<MediaKind>,
+    /// Link to the original message, if the message is a forward
+    pub forwarded_from: Option<String>,
+    /// Position of the message in the channel. Higher is newer.
+    /// The newest message at backfill time is 0, older ones are negative
+    pub position: i64,
+    /// Which part of the image to keep when cropping, if the poster or a moderator said so
+    #[serde(default)]
+    pub crop_hint: Option<CropHint>,
+}
+
+impl IndexedMedia {
+    pub fn new(media: &MediaWithMessage, nth: usize, position: i64, crop_hint: Option<CropHint>) -> Self {
+        Self {
+            url: media.media.to_string(),
+            channel_id: media.message.channel_id.get(),
+            message_id: media.message.id.get(),
+            nth,
+            author_id: media.message.author.id.get(),
+            width: media.dimensions.map(|(width, _)| width),
+            height: media.dimensions.map(|(_, height)| height),
+            kind: media.kind,
+            forwarded_from: media.forwarded_from.map(|original| original.link()),
+            position,
+            crop_hint,
+        }
+    }
+
+    /// Field name inside the channel's index
+    fn field(&self) -> String {
+        format!("{}:{}", self.message_id, self.nth)
+    }
+}
+
+impl Database {
+    /// Hash of `message_id:nth` to json encoded [IndexedMedia]
+    fn media_index_key(&self, channel_id: u64) -> String {
+        self.key(format!("media_index:{channel_id}"))
+    }
+
+    /// Counter of messages seen in a channel since the backfill started
+    fn media_index_position_key(&self, channel_id: u64) -> String {
+        self.key(format!("media_index_position:{channel_id}"))
+    }
+
+    /// How many messages the backfill of the channel looked at so far.
+    /// `None` if it never started
+    pub async fn media_index_depth(&self, channel_id: u64) -> Result<Option<u64>, Error> {
+        self.client.hget(self.key("indexed_channels"), channel_id).await
+    }
+
+    /// Oldest message the backfill of the channel looked at, the backfill continues from there.
+    /// `None` if it never started or the channel was indexed before backfills could be continued
+    pub async fn media_index_cursor(&self, channel_id: u64) -> Result<Option<u64>, Error> {
+        self.client
+            .hget(self.key("indexed_channels"), format!("{channel_id}:cursor"))
+            .await
+    }
+
+    /// Newest message of the channel that was indexed.
+    /// `None` if the channel was indexed before this was kept track of
+    pub async fn media_index_head(&self, channel_id: u64) -> Result<Option<u64>, Error> {
+        self.client
+            .hget(self.key("indexed_channels"), format!("{channel_id}:head"))
+            .await
+    }
+
+    /// Remember the newest message of the channel that was indexed
+    pub async fn set_media_index_head(&self, channel_id: u64, message_id: u64) -> Result<(), Error> {
+        self.client
+            .hset(
+                self.key("indexed_channels"),
+                (format!("{channel_id}:head"), message_id.to_string()),
+            )
+            .await
+    }
+
+    /// All channels that have a media index
+    pub async fn indexed_channels(&self) -> Result<Vec<u64>, Error> {
+        let fields: Vec<String> = self.client.hkeys(self.key("indexed_channels")).await?;
+
+        // the other fields are named `CHANNEL_ID:something`
+        Ok(fields.iter().filter_map(|field| field.parse().ok()).collect())
+    }
+
+    /// Save how far the backfill of the channel got.
+    /// From the first call on, gateway events keep the index up to date
+    pub async fn set_media_index_progress(
+        &self,
+        channel_id: u64,
+        depth: u64,
+        cursor: u64,
+    ) -> Result<(), Error> {
+        // a single HSET, so depth and cursor always belong together
+        let mut map = HashMap::with_capacity(2);
+        map.insert(channel_id.to_string(), depth.to_string());
+        map.insert(format!("{channel_id}:cursor"), cursor.to_string());
+        self.client.hset(self.key("indexed_channels"), map).await
+    }
+
+    /// Remove everything that is known about the channel
+    pub async fn drop_media_index(&self, channel_id: u64) -> Result<(), Error> {
+        let _: () = self
+            .client
+            .hdel(
+                self.key("indexed_channels"),
+                vec![
+                    channel_id.to_string(),
+                    format!("{channel_id}:cursor"),
+                    format!("{channel_id}:head"),
+                ],
+            )
+            .await?;
+        let _: () = self.client.del(self.media_index_key(channel_id)).await?;
+        let _: () = self.client.del(self.media_index_position_key(channel_id)).await?;
+        self.drop_image_hashes(channel_id).await
+    }
+
+    /// Position of the newest message in the channel
+    pub async fn media_index_position(&self, channel_id: u64) -> Result<i64, Error> {
+        let position: Option<i64> = self.client.get(self.media_index_position_key(channel_id)).await?;
+        Ok(position.unwrap_or_default())
+    }
+
+    /// Count a new message and return its position
+    pub async fn next_media_index_position(&self, channel_id: u64) -> Result<i64, Error> {
+        self.client.incr(self.media_index_position_key(channel_id)).await
+    }
+
+    /// Add media to the index
+    pub async fn index_media(&self, channel_id: u64, entries: &[IndexedMedia]) -> Result<(), Error> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut map = HashMap::with_capacity(entries.len());
+        for entry in entries {
+            let value = serde_json::to_string(entry)
+                .map_err(|err| Error::new(fred::error::ErrorKind::Parse, err.to_string()))?;
+            map.insert(entry.field(), value);
+        }
+
+        self.client.hset(self.media_index_key(channel_id), map).await
+    }
+
+    /// Fill in the kind of an indexed media, once its bytes told
+    ///
+    /// Does nothing if the entry was removed or edited since.
+    pub async fn set_indexed_media_kind(&self, entry: &IndexedMedia, kind: MediaKind) -> Result<(), Error> {
+        let current: Option<String> = self
+            .client
+            .hget(self.media_index_key(entry.channel_id), entry.field())
+            .await?;
+        let Some(mut current) = current.and_then(|value| serde_json::from_str::<IndexedMedia>(&value).ok())
+        else {
+            return Ok(());
+        };
+        if current.url != entry.url {
+            return Ok(());
+        }
+
+        current.kind = Some(kind);
+        self.index_media(entry.channel_id, &[current]).await
+    }
+
+    /// All media in the index of the channel
+    pub async fn indexed_media(&self, channel_id: u64) -> Result<Vec<IndexedMedia>, Error> {
+        let values: Vec<String> = self.client.hvals(self.media_index_key(channel_id)).await?;
+
+        Ok(values
+            .iter()
+            .filter_map(|value| match serde_json::from_str(value) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    warn!("Skipping broken media index entry in channel {channel_id}: {err}");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// All media of a single message in the index of the channel
+    pub async fn indexed_media_of_message(
+        &self,
+        channel_id: u64,
+        message_id: u64,
+    ) -> Result<Vec<IndexedMedia>, Error> {
+        let fields = self.media_index_fields_of(channel_id, &[message_id]).await?;
+        if fields.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let values: Vec<Option<String>> = self
+            .client
+            .hmget(self.media_index_key(channel_id), fields)
+            .await?;
+
+        Ok(values
+            .iter()
+            .flatten()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .collect())
+    }
+
+    /// Remove all media of these messages from the index
+    pub async fn remove_indexed_messages(&self, channel_id: u64, message_ids: &[u64]) -> Result<(), Error> {
+        let fields = self.media_index_fields_of(channel_id, message_ids).await?;
+        self.remove_media_index_fields(channel_id, fields).await
+    }
+
+    /// Remove these entries from the index
+    pub async fn remove_indexed_media(&self, channel_id: u64, entries: &[IndexedMedia]) -> Result<(), Error> {
+        let fields = entries.iter().map(IndexedMedia::field).collect();
+        self.remove_media_index_fields(channel_id, fields).await
+    }
+
+    /// Field names in the index belonging to these messages
+    async fn media_index_fields_of(
+        &self,
+        channel_id: u64,
+        message_ids: &[u64],
+    ) -> Result<Vec<String>, Error> {
+        let fields: Vec<String> = self.client.hkeys(self.media_index_key(channel_id)).await?;
+
+        Ok(fields
+            .into_iter()
+            .filter(|field| {
+                field
+                    .split_once(':')
+                    .and_then(|(message_id, _)| message_id.parse().ok())
+                    .is_some_and(|message_id: u64| message_ids.contains(&message_id))
+            })
+            .collect())
+    }
+
+    async fn remove_media_index_fields(&self, channel_id: u64, fields: Vec<String>) -> Result<(), Error> {
+        // HDEL without fields is an error
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        self.client.hdel(self.media_index_key(channel_id), fields).await
+    }
+}
@@ -1,14 +1,17 @@
 //! here be database stuff
 
+pub mod corrupt_images;
+pub mod deduplication;
 pub mod guild_schedule;
 pub mod guild_settings;
+pub mod media_index;
 
 use std::{borrow::Cow, sync::Arc};
 
 use fred::{
     error::Error as RedisError,
     prelude::*,
-    types::{ConnectHandle, FromValue, Key, Map},
+    types::{ConnectHandle, FromValue, Key, Map, Value},
 };
 use tracing::info;
 
@@ -119,8 +122,8 @@ impl Database {
         format!("{}:{}", self.prefix, key)
     }
 
-    /// List of guild ids that have an active schedule going
-    pub async fn active_schedules(&self) -> Result<Vec<u64>, RedisError> {
+    /// Keys of all active schedules. See [ScheduleKey](crate::schedule_target::ScheduleKey)
+    pub async fn active_schedules(&self) -> Result<Vec<String>, RedisError> {
         self.client.smembers(self.key("active_schedules")).await
     }
 
@@ -161,3 +164,10 @@ fn get_from_redis_map<T: FromValue>(map: &Map, key: &str) -> Result<T, RedisErro
         .clone()
         .convert()
 }
+
+/// Get the value with `key` from a [RedisMap](RedisMap) `map`, if it exists.
+///
+/// Used for fields that were added later on and might be missing in older entries.
+fn get_optional_from_redis_map<T: FromValue>(map: &Map, key: &str) -> Result<Option<T>, RedisError> {
+    map.get(&Key::from(key)).cloned().map(Value::convert).transpose()
+}
@@ -0,0 +1,244 @@
+//! Finding images that were posted more than once
+//!
+//! Images are hashed in the background when they are indexed, a few at a time.
+//! Hashes are stored per channel and forgotten once the image leaves the channel's index.
+//! Before picking a new banner, all candidates sharing a hash are collapsed into one,
+//! so reposted images are not picked more often than others.
+//!
+//! The same download tells the kind of media the index could not guess from the url, like animated webp.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use image::imageops::FilterType;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+use tracing::{debug, instrument, warn};
+use url::Url;
+
+use crate::{
+    Error, Settings,
+    constants::MAXIMUM_DOWNLOAD_SIZE,
+    database::{Database, media_index::IndexedMedia},
+    media_kind::MediaKind,
+};
+
+/// Shared by every background task, so there are never more than `concurrent_downloads` downloads for hashing
+static HASHING_PERMITS: LazyLock<Semaphore> =
+    LazyLock::new(|| Semaphore::new(Settings::get().deduplication.concurrent_downloads.max(1)));
+
+/// Hashes of a single downloaded image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageHashes {
+    /// Sha256 of the file, hex encoded
+    pub content: String,
+    /// Difference hash of the first frame. Only computed if enabled in the settings
+    pub perceptual: Option<u64>,
+}
+
+impl ImageHashes {
+    /// Hash the bytes of an image. This is cpu heavy when `perceptual` is set
+    pub fn compute(bytes: &[u8], perceptual: bool) -> Self {
+        Self {
+            content: format!("{:x}", Sha256::digest(bytes)),
+            perceptual: perceptual.then(|| difference_hash(bytes)).flatten(),
+        }
+    }
+}
+
+/// The same image, no matter which signature is attached to the url
+///
+/// For discord attachments the path contains the attachment id.
+pub fn media_identity(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => format!("{}{}", url.host_str().unwrap_or_default(), url.path()),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Keep only one entry per image. Newer posts are preferred over older ones.
+///
+/// Returns the remaining entries and how many were dropped.
+#[instrument(skip_all)]
+pub async fn collapse_duplicates(
+    db: &Database,
+    mut pool: Vec<IndexedMedia>,
+) -> Result<(Vec<IndexedMedia>, usize), Error> {
+    let settings = &Settings::get().deduplication;
+
+    // message ids are snowflakes, so this sorts by time. works across channels
+    pool.sort_unstable_by(|a, b| b.message_id.cmp(&a.message_id));
+
+    let identities: Vec<String> = pool.iter().map(|entry| media_identity(&entry.url)).collect();
+
+    // hashes are stored per channel, the pool can span many
+    let mut by_channel: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, entry) in pool.iter().enumerate() {
+        by_channel.entry(entry.channel_id).or_default().push(index);
+    }
+
+    let mut hashes = vec![None; pool.len()];
+    for (channel_id, indices) in by_channel {
+        let channel_identities: Vec<String> =
+            indices.iter().map(|&index| identities[index].clone()).collect();
+        let found = db.image_hashes(channel_id, &channel_identities).await?;
+        for (index, found) in indices.into_iter().zip(found) {
+            hashes[index] = found;
+        }
+    }
+
+    let mut seen_identities = HashSet::with_capacity(pool.len());
+    let mut seen_content = HashSet::with_capacity(pool.len());
+    let mut seen_perceptual: Vec<u64> = Vec::new();
+
+    let before = pool.len();
+    let mut kept = Vec::with_capacity(pool.len());
+
+    for ((entry, identity), hashes) in pool.into_iter().zip(identities).zip(hashes) {
+        if !seen_identities.insert(identity) {
+            continue;
+        }
+
+        if let Some(hashes) = hashes {
+            if !seen_content.insert(hashes.content) {
+                continue;
+            }
+
+            if let Some(perceptual) = hashes.perceptual.filter(|_| settings.perceptual) {
+                if seen_perceptual
+                    .iter()
+                    .any(|seen| (seen ^ perceptual).count_ones() <= settings.perceptual_distance)
+                {
+                    continue;
+                }
+                seen_perceptual.push(perceptual);
+            }
+        }
+
+        kept.push(entry);
+    }
+
+    let duplicates = before - kept.len();
+    debug!("Collapsed {duplicates} duplicates out of {before} images");
+
+    Ok((kept, duplicates))
+}
+
+/// Download and hash images that are not known yet, and fill in the kind of media where the index
+/// has none. Runs in its own task, downloads wait for a permit so many tasks at once don't flood the network.
+///
+/// Failing to hash an image is not a problem, it just won't be deduplicated.
+pub fn hash_in_background(db: Database, reqw_client: Client, entries: Vec<IndexedMedia>) {
+    let settings = &Settings::get().deduplication;
+    let entries: Vec<IndexedMedia> = entries
+        .into_iter()
+        .filter(|entry| settings.enabled || entry.kind.is_none())
+        .collect();
+    if entries.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        for entry in entries {
+            let identity = media_identity(&entry.url);
+
+            let needs_hashes = settings.enabled
+                && match db
+                    .image_hashes(entry.channel_id, std::slice::from_ref(&identity))
+                    .await
+                {
+                    Ok(known) => known.first().is_none_or(Option::is_none),
+                    Err(err) => {
+                        warn!("Could not look up image hashes: {err}");
+                        return;
+                    }
+                };
+            if !needs_hashes && entry.kind.is_some() {
+                continue;
+            }
+
+            let Ok(_permit) = HASHING_PERMITS.acquire().await else {
+                return;
+            };
+
+            let bytes = match download(&reqw_client, &entry.url).await {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => continue,
+                Err(err) => {
+                    debug!("Could not download {} for hashing: {err}", entry.url);
+                    continue;
+                }
+            };
+
+            if entry.kind.is_none()
+                && let Some(kind) = MediaKind::sniff(&bytes)
+                && let Err(err) = db.set_indexed_media_kind(&entry, kind).await
+            {
+                warn!("Could not update the kind of indexed media: {err}");
+            }
+
+            if !needs_hashes {
+                continue;
+            }
+
+            let perceptual = settings.perceptual;
+            let Ok(hashes) =
+                tokio::task::spawn_blocking(move || ImageHashes::compute(&bytes, perceptual)).await
+            else {
+                continue;
+            };
+
+            if let Err(err) = db.cache_image_hashes(entry.channel_id, &identity, &hashes).await {
+                warn!("Could not cache image hashes: {err}");
+                return;
+            }
+        }
+    });
+}
+
+/// Download an image, giving up on anything too big to be shrunk into a banner
+async fn download(reqw_client: &Client, url: &str) -> Result<Option<Vec<u8>>, reqwest::Error> {
+    let mut response = reqw_client.get(url).send().await?.error_for_status()?;
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > MAXIMUM_DOWNLOAD_SIZE {
+            return Ok(None);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(bytes))
+}
+
+/// 64 bit difference hash. Each bit says whether a pixel is brighter than its right neighbour,
+/// on a 9x8 grayscale version of the image
+fn difference_hash(bytes: &[u8]) -> Option<u64> {
+    let image = image::load_from_memory(bytes).ok()?;
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Some(hash)
+}
+
+/// How many images a schedule had to choose from on its last run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Images left after collapsing duplicates
+    pub candidates: usize,
+    /// Images dropped because they were duplicates
+    pub duplicates: usize,
+}
@@ -2,6 +2,7 @@ use std::{
     collections::HashSet,
     fmt::{Debug, Display},
     num::NonZeroU16,
+    sync::Arc,
 };
 
 use chrono::{DateTime, Utc};
@@ -14,11 +15,15 @@ use thiserror::Error;
 use tracing::{info, instrument, warn};
 
 use crate::{
-    Settings,
+    Settings, State,
+    deduplication::media_identity,
+    finding_media::OriginalMessage,
+    localization::{Language, command_language, format, guild_language, tr},
+    notification::notify_guild,
+    owner_digest::{Severity, notify_owners},
     schedule_runner::{RunnerError, ScheduleAction},
     setting_banner::SetBannerError,
     settings::SettingsError,
-    utils::{dm_user, dm_users},
 };
 
 #[derive(Debug, Error)]
@@ -54,36 +59,116 @@ pub enum Error {
     Timeout { action: String },
 }
 
+impl Error {
+    /// Short name of the kind of error, for webhooks and logs
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Settings(_) => "settings",
+            Self::Reqwest(_) => "reqwest",
+            Self::Serenity(_) => "discord_api",
+            Self::Redis(_) => "database",
+            Self::Command(_) => "command",
+            Self::Scheduler { .. } => "scheduler",
+            Self::StdFmt(_) => "formatting",
+            Self::SendDm(_) => "send_dm",
+            Self::SetBanner(err) => err.category(),
+            Self::Timeout { .. } => "timeout",
+        }
+    }
+
+    /// How urgently the bot owners need to know about this error
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Scheduler { .. } => Severity::Critical,
+            Self::Settings(_) | Self::Redis(_) | Self::StdFmt(_) => Severity::Error,
+            Self::Reqwest(_) | Self::Serenity(_) | Self::SendDm(_) | Self::Timeout { .. } => {
+                Severity::Warning
+            }
+            Self::Command(_) => Severity::Info,
+            Self::SetBanner(err) => err.severity(),
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Command {
-    #[error("Command must be run in a server")]
     GuildOnly,
-
-    #[error("Server doesn't have a banner set")]
     GuildHasNoBannerSet,
-
-    #[error("Server doesn't have the required boost level")]
     GuildHasNoBannerFeature,
-
-    #[error("Interval must be at least {} minutes", Settings::get().scheduler.minimum_interval)]
+    GuildHasNoAnimatedBannerFeature,
+    GuildHasNoAnimatedIconFeature,
+    GuildHasNoInviteSplashFeature,
+    GuildIsNotDiscoverable,
+    SplashCannotBeAnimated,
     BelowMinTimeout,
-
-    #[error("Interval must be at most {} minutes", Settings::get().scheduler.maximum_interval)]
     AboveMaxTimeout,
-
-    #[error("Message limit must be greater than 0")]
     MessageLimitIszero,
-
-    #[error("Message limit must be at most {}", Settings::get().scheduler.maximum_message_limit)]
     AboveMaxMessageLimit,
-
-    #[error("Start time cannot be in the past. Now={now}, given={given}")]
+    LookbackDaysIsZero,
+    AboveMaxLookbackDays,
     StartTimeInThePast {
         now: DateTime<Utc>,
         given: DateTime<Utc>,
     },
 }
 
+impl Command {
+    /// The message shown to the user
+    pub fn localized(&self, language: Language) -> String {
+        let scheduler = &Settings::get().scheduler;
+        match self {
+            Self::GuildOnly => tr(language, "error.guild_only").to_string(),
+            Self::GuildHasNoBannerSet => tr(language, "error.guild_has_no_banner_set").to_string(),
+            Self::GuildHasNoBannerFeature => tr(language, "error.guild_has_no_banner_feature").to_string(),
+            Self::GuildHasNoAnimatedBannerFeature => {
+                tr(language, "error.guild_has_no_animated_banner_feature").to_string()
+            }
+            Self::GuildHasNoAnimatedIconFeature => {
+                tr(language, "error.guild_has_no_animated_icon_feature").to_string()
+            }
+            Self::GuildHasNoInviteSplashFeature => {
+                tr(language, "error.guild_has_no_invite_splash_feature").to_string()
+            }
+            Self::GuildIsNotDiscoverable => tr(language, "error.guild_is_not_discoverable").to_string(),
+            Self::SplashCannotBeAnimated => tr(language, "error.splash_cannot_be_animated").to_string(),
+            Self::BelowMinTimeout => format(
+                language,
+                "error.below_min_interval",
+                &[("minutes", &scheduler.minimum_interval)],
+            ),
+            Self::AboveMaxTimeout => format(
+                language,
+                "error.above_max_interval",
+                &[("minutes", &scheduler.maximum_interval)],
+            ),
+            Self::MessageLimitIszero => tr(language, "error.message_limit_is_zero").to_string(),
+            Self::AboveMaxMessageLimit => format(
+                language,
+                "error.above_max_message_limit",
+                &[("limit", &scheduler.maximum_message_limit)],
+            ),
+            Self::LookbackDaysIsZero => tr(language, "error.lookback_days_is_zero").to_string(),
+            Self::AboveMaxLookbackDays => format(
+                language,
+                "error.above_max_lookback_days",
+                &[("days", &scheduler.maximum_lookback_days)],
+            ),
+            Self::StartTimeInThePast { now, given } => format(
+                language,
+                "error.start_time_in_the_past",
+                &[("now", now), ("given", given)],
+            ),
+        }
+    }
+}
+
+/// English, for logs. Users see [Command::localized]
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.localized(Language::English))
+    }
+}
+
 /// Error when sending direct messages to a user
 #[derive(Debug, thiserror::Error)]
 pub struct SendDm {
@@ -145,13 +230,24 @@ impl Display for SendDm {
 /// Handles framework related errors.
 /// Does __not__ handle scheduler related errors
 #[instrument(skip_all)]
-pub async fn handle_framework_error<U, E>(error: poise::FrameworkError<'_, U, E>) -> Result<(), Error>
-where
-    U: Send + Sync + 'static,
-    E: Into<Box<dyn std::error::Error + Send + Sync>> + std::fmt::Display,
-{
+pub async fn handle_framework_error(error: poise::FrameworkError<'_, State, Error>) -> Result<(), Error> {
     tracing::error!("{}", &error);
 
+    // answer in the user's language, everything else is handled by poise
+    if let poise::FrameworkError::Command {
+        error: Error::Command(command_error),
+        ctx,
+        ..
+    } = &error
+    {
+        let language = command_language(*ctx).await?;
+        let reply = poise::CreateReply::default()
+            .content(command_error.localized(language))
+            .ephemeral(true);
+        poise::send_reply(*ctx, reply).await?;
+        return Ok(());
+    }
+
     poise::builtins::on_error(error).await?;
 
     Ok(())
@@ -172,14 +268,7 @@ pub async fn evaluate_schedule_error(
 
     let guild_name = format!("{guild_id}: {}", guild_id.name(&ctx.cache).unwrap_or_default());
 
-    let message = MessageBuilder::new()
-        .push_bold("Error in guild: ")
-        .push_line_safe(&*guild_name)
-        .push_line("")
-        .push(&*error.to_string())
-        .build();
-
-    dm_users(&ctx, owners.clone(), &message).await?;
+    notify_owners(&ctx, owners, error, &guild_name).await?;
 
     match error.source() {
         Error::Serenity(serenity_error) => match serenity_error {
@@ -253,31 +342,73 @@ pub async fn evaluate_schedule_error(
                 SetBannerError::CouldNotPickAUrl => {
                     warn!("guild_id={guild_id}: 'Could not pick a url'. RNG failed")
                 }
-                SetBannerError::CouldNotDeterminFileExtension(url) => {
-                    warn!("guild_id={guild_id}: 'Could not determine file extenstion. url={url}'");
+                SetBannerError::UnknownImageFormat(url, ..) => {
+                    warn!(
+                        "guild_id={guild_id} with channel={channel_id} has selected an image of unknown format. url={url}"
+                    );
                     return Ok(ScheduleAction::RetryNewImage);
                 }
                 SetBannerError::MissingBannerFeature => {
-                    let partial_guild = guild_id.to_partial_guild(&ctx.http).await?;
-                    let guild_owner = partial_guild.owner_id;
-                    warn!(
-                        "Letting owner={guild_owner} of guild={guild_id} know about the missing banner feature"
-                    );
+                    warn!("Letting guild={guild_id} know about the missing banner feature");
+
+                    let language = guild_language(&ctx, guild_id).await?;
+                    notify_guild(&ctx, guild_id, tr(language, "notify.lost_banner_feature")).await?;
+                    return Ok(ScheduleAction::Abort);
+                }
+                SetBannerError::MissingInviteSplashFeature => {
+                    warn!("Letting guild={guild_id} know about the missing invite splash feature");
+
+                    let language = guild_language(&ctx, guild_id).await?;
+                    notify_guild(&ctx, guild_id, tr(language, "notify.lost_invite_splash_feature")).await?;
+                    return Ok(ScheduleAction::Abort);
+                }
+                SetBannerError::MissingDiscoverableFeature => {
+                    warn!("Letting guild={guild_id} know about the missing discoverable feature");
 
-                    dm_user(&ctx, guild_owner, "Server has lost the required boost level. Stopping schedule. You can restart the bot after gaining the required boost level.").await?;
+                    let language = guild_language(&ctx, guild_id).await?;
+                    notify_guild(&ctx, guild_id, tr(language, "notify.not_discoverable")).await?;
                     return Ok(ScheduleAction::Abort);
                 }
                 SetBannerError::MissingAnimatedBannerFeature(url, ..) => {
                     warn!(
                         "guild_id={guild_id} with channel={channel_id} was trying to set an animated banner but does not have the feature. url={url}"
                     );
-                    let partial_guild = guild_id.to_partial_guild(&ctx.http).await?;
-                    let guild_owner = partial_guild.owner_id;
+                    warn!("Letting guild={guild_id} know about the missing animated banner feature");
+
+                    let language = guild_language(&ctx, guild_id).await?;
+                    notify_guild(
+                        &ctx,
+                        guild_id,
+                        tr(language, "notify.missing_animated_banner_feature"),
+                    )
+                    .await?;
+                    return Ok(ScheduleAction::RetryNewImage);
+                }
+                SetBannerError::MissingAnimatedIconFeature(url, ..) => {
                     warn!(
-                        "Letting owner={guild_owner} of guild={guild_id} know about the missing animated banner feature"
+                        "guild_id={guild_id} with channel={channel_id} was trying to set an animated icon but does not have the feature. url={url}"
+                    );
+                    warn!("Letting guild={guild_id} know about the missing animated icon feature");
+
+                    let language = guild_language(&ctx, guild_id).await?;
+                    notify_guild(
+                        &ctx,
+                        guild_id,
+                        tr(language, "notify.missing_animated_icon_feature"),
+                    )
+                    .await?;
+                    return Ok(ScheduleAction::RetryNewImage);
+                }
+                SetBannerError::AnimatedSplash(url, ..) => {
+                    warn!(
+                        "guild_id={guild_id} with channel={channel_id} was trying to set an animated splash. url={url}"
+                    );
+                    return Ok(ScheduleAction::RetryNewImage);
+                }
+                SetBannerError::FilteredMediaKind(url, ..) => {
+                    info!(
+                        "guild_id={guild_id} with channel={channel_id} has selected an image its media filter does not allow. url={url}"
                     );
-
-                    dm_user(&ctx, guild_owner, &format!("Tried to set an animated banner but the server '{}' does not have the required boost level for animated banners", partial_guild.name)).await?;
                     return Ok(ScheduleAction::RetryNewImage);
                 }
                 SetBannerError::ImageIsEmpty(url, ..) => {
@@ -291,21 +422,30 @@ pub async fn evaluate_schedule_error(
                         "guild_id={guild_id} with channel={channel_id} has selected an image that is too big. url={url}"
                     );
 
-                    let partial_guild = guild_id.to_partial_guild(&ctx.http).await?;
-                    let guild_owner = partial_guild.owner_id;
-                    info!(
-                        "Letting owner={guild_owner} of guild={guild_id} know about an image that is too big"
-                    );
+                    info!("Letting guild={guild_id} know about an image that is too big");
+
+                    let language = guild_language(&ctx, guild_id).await?;
+                    let mut message_builder = MessageBuilder::new()
+                        .push_line(tr(language, "notify.image_too_big"))
+                        .push_line(&*format(
+                            language,
+                            "notify.image_message",
+                            &[("link", &message.link())],
+                        ));
+
+                    if let Some(original) = OriginalMessage::from_message(message) {
+                        message_builder = message_builder.push_line(&*format(
+                            language,
+                            "notify.forwarded_from",
+                            &[("link", &original.link())],
+                        ));
+                    }
 
-                    let message = MessageBuilder::new()
-                        .push_line("An image is too big. Discord allows a maximum of 10mb for banners. Consider deleting it.")
-                        .push("The image is in this message: ")
-                        .push_line(message.link().to_string().as_str())
-                        .push("This is the image: ")
-                        .push_line(url.to_string().as_str())
+                    let message = message_builder
+                        .push_line(&*format(language, "notify.image_url", &[("url", url)]))
                         .build();
 
-                    dm_user(&ctx, guild_owner, &message).await?;
+                    notify_guild(&ctx, guild_id, &message).await?;
                     return Ok(ScheduleAction::RetryNewImage);
                 }
                 SetBannerError::ImageUnkownSize(url, ..) => {
@@ -320,6 +460,25 @@ pub async fn evaluate_schedule_error(
                     );
                     return Ok(ScheduleAction::RetryNewImage);
                 }
+                SetBannerError::ImageProcessing(url, ..) => {
+                    warn!(
+                        "guild_id={guild_id} with channel={channel_id} has selected an image which could not be cropped or resized. url={url}"
+                    );
+                    return Ok(ScheduleAction::RetryNewImage);
+                }
+                SetBannerError::ImageIsCorrupt(url, ..) => {
+                    warn!(
+                        "guild_id={guild_id} with channel={channel_id} has selected an image which could not be decoded. url={url}"
+                    );
+
+                    // a broken file stays broken, don't pick it again for a while
+                    let state: Arc<State> = ctx.data();
+                    state
+                        .database()
+                        .mark_image_as_corrupt(&media_identity(url.as_str()))
+                        .await?;
+                    return Ok(ScheduleAction::RetryNewImage);
+                }
             }
         }
         Error::Timeout { action } => {
@@ -3,7 +3,17 @@ use std::sync::Arc;
 use poise::serenity_prelude::{Context, EventHandler, FullEvent, async_trait};
 use tracing::{debug, error, info, instrument, warn};
 
-use crate::{Error, startup::handle_event_ready, state::State, utils::dm_users};
+use crate::{
+    Error,
+    crop_hint::CropHint,
+    indexing::{
+        catch_up_in_background, index_new_message, reindex_message, unindex_messages, update_crop_hint,
+    },
+    startup::handle_event_ready,
+    state::State,
+    utils::dm_users,
+    webhook::{self, Event, EventKind},
+};
 
 pub struct Handler;
 
@@ -24,8 +34,14 @@ pub async fn handle_event(context: &Context, event: &FullEvent) -> Result<(), Er
         FullEvent::Ready { data_about_bot, .. } => {
             if context.data::<State>().is_initialized() {
                 debug!("Ready event fired but already initialized. Skipping setup, but reloading schedules");
-                let result = context.data::<State>().load_schedules_from_db().await?;
+                let state: Arc<State> = context.data();
+                let result = state.load_schedules_from_db().await?;
                 info!("{result}");
+                catch_up_in_background(
+                    context.http.clone(),
+                    state.database().clone(),
+                    state.reqw_client().clone(),
+                );
                 return Ok(());
             }
             handle_event_ready(context.to_owned(), data_about_bot).await
@@ -45,7 +61,12 @@ pub async fn handle_event(context: &Context, event: &FullEvent) -> Result<(), Er
                 error!("GuildDelete event fired before bot was initialized");
             }
 
-            state.deque(incomplete.id).await?;
+            for schedule in state.deque_guild(incomplete.id).await? {
+                webhook::emit(
+                    state.reqw_client(),
+                    Event::new(EventKind::ScheduleStopped, &schedule),
+                );
+            }
             Ok(())
         }
         FullEvent::Resume { event, .. } => {
@@ -60,6 +81,11 @@ pub async fn handle_event(context: &Context, event: &FullEvent) -> Result<(), Er
             info!("Loading schedules from database");
             let result = state.load_schedules_from_db().await?;
             info!("{result}");
+            catch_up_in_background(
+                context.http.clone(),
+                state.database().clone(),
+                state.reqw_client().clone(),
+            );
             Ok(())
         }
         FullEvent::ShardStageUpdate { event, .. } => {
@@ -70,6 +96,85 @@ pub async fn handle_event(context: &Context, event: &FullEvent) -> Result<(), Er
             debug!("ShardsReady: {total_shards:?}");
             Ok(())
         }
+        FullEvent::Message { new_message, .. } => {
+            let state: Arc<State> = context.data();
+            index_new_message(state.database(), state.reqw_client(), new_message).await
+        }
+        FullEvent::MessageUpdate { event, .. } => {
+            let state: Arc<State> = context.data();
+            reindex_message(state.database(), state.reqw_client(), &event.message).await
+        }
+        FullEvent::MessageDelete {
+            channel_id,
+            deleted_message_id,
+            ..
+        } => {
+            let state: Arc<State> = context.data();
+            unindex_messages(state.database(), *channel_id, &[*deleted_message_id]).await
+        }
+        FullEvent::MessageDeleteBulk {
+            channel_id,
+            multiple_deleted_messages_ids,
+            ..
+        } => {
+            let state: Arc<State> = context.data();
+            unindex_messages(state.database(), *channel_id, multiple_deleted_messages_ids).await
+        }
+        FullEvent::ReactionAdd { add_reaction, .. }
+            if CropHint::from_reaction(&add_reaction.emoji).is_some() =>
+        {
+            let state: Arc<State> = context.data();
+            update_crop_hint(
+                context,
+                state.database(),
+                add_reaction.guild_id,
+                add_reaction.channel_id,
+                add_reaction.message_id,
+            )
+            .await
+        }
+        FullEvent::ReactionRemove { removed_reaction, .. }
+            if CropHint::from_reaction(&removed_reaction.emoji).is_some() =>
+        {
+            let state: Arc<State> = context.data();
+            update_crop_hint(
+                context,
+                state.database(),
+                removed_reaction.guild_id,
+                removed_reaction.channel_id,
+                removed_reaction.message_id,
+            )
+            .await
+        }
+        FullEvent::ReactionRemoveEmoji {
+            removed_reactions, ..
+        } if CropHint::from_reaction(&removed_reactions.emoji).is_some() => {
+            let state: Arc<State> = context.data();
+            update_crop_hint(
+                context,
+                state.database(),
+                removed_reactions.guild_id,
+                removed_reactions.channel_id,
+                removed_reactions.message_id,
+            )
+            .await
+        }
+        FullEvent::ReactionRemoveAll {
+            channel_id,
+            removed_from_message_id,
+            ..
+        } => {
+            // no reactions are left, so nobody needs to be checked
+            let state: Arc<State> = context.data();
+            update_crop_hint(
+                context,
+                state.database(),
+                None,
+                *channel_id,
+                *removed_from_message_id,
+            )
+            .await
+        }
         FullEvent::ChannelDelete { channel, .. } => {
             // if the channel that contains the banners of a guild is deleted
             // then unschedule the guild
@@ -77,14 +182,16 @@ pub async fn handle_event(context: &Context, event: &FullEvent) -> Result<(), Er
             debug!("ChannelDelete: {channel:?}");
 
             let state: Arc<State> = context.data();
-            let schedule = match state.get_schedule(channel.base.guild_id).await {
-                Ok(Some(schedule)) => schedule,
-                Ok(None) => return Ok(()),
-                Err(err) => return Err(err)?,
-            };
-
-            if channel.base.guild_id == schedule.guild_id() && channel.id.widen() == schedule.channel_id() {
-                state.deque(channel.base.guild_id).await?;
+            state.database().drop_media_index(channel.id.get()).await?;
+
+            for schedule in state.get_schedules_of_guild(channel.base.guild_id).await? {
+                if channel.id.widen() == schedule.channel_id() {
+                    state.deque(schedule.key()).await?;
+                    webhook::emit(
+                        state.reqw_client(),
+                        Event::new(EventKind::ScheduleStopped, &schedule),
+                    );
+                }
             }
 
             Ok(())
@@ -93,14 +200,16 @@ pub async fn handle_event(context: &Context, event: &FullEvent) -> Result<(), Er
             debug!("ThreadDelete: {thread:?}");
 
             let state: Arc<State> = context.data();
-            let schedule = match state.get_schedule(thread.guild_id).await {
-                Ok(Some(schedule)) => schedule,
-                Ok(None) => return Ok(()),
-                Err(err) => return Err(err)?,
-            };
-
-            if thread.guild_id == schedule.guild_id() && thread.id.widen() == schedule.channel_id() {
-                state.deque(thread.guild_id).await?;
+            state.database().drop_media_index(thread.id.get()).await?;
+
+            for schedule in state.get_schedules_of_guild(thread.guild_id).await? {
+                if thread.id.widen() == schedule.channel_id() {
+                    state.deque(schedule.key()).await?;
+                    webhook::emit(
+                        state.reqw_client(),
+                        Event::new(EventKind::ScheduleStopped, &schedule),
+                    );
+                }
             }
 
             Ok(())
@@ -1,25 +1,86 @@
 use std::{fmt::Display, num::NonZeroU32};
 
 use poise::serenity_prelude::{
-    CacheHttp, Error, GenericChannelId, Message, futures::stream as futures_stream,
-    small_fixed_array::FixedString,
+    Attachment, CacheHttp, Channel, ChannelId, ChannelType, Embed, Error, GenericChannelId, GuildId, Message,
+    MessageId, ThreadId, futures::stream as futures_stream, small_fixed_array::FixedString,
 };
 use tokio_stream::{Stream, StreamExt};
-use tracing::instrument;
+use tracing::{debug, instrument};
+use url::Url;
 
-use crate::schedule::Schedule;
+use crate::{Settings, media_kind::MediaKind, schedule::Schedule};
 
 #[derive(Debug)]
 pub struct MediaWithMessage {
     pub media: FixedString,
+    /// Best guess before downloading. `None` if it could not be determined
+    pub kind: Option<MediaKind>,
+    /// The message containing the media. For forwarded messages that's the forwarding one
     pub message: Message,
+    /// Where the media was originally posted, if `message` is a forward
+    pub forwarded_from: Option<OriginalMessage>,
+    /// Width and height, if discord told us
+    pub dimensions: Option<(u32, u32)>,
 }
 
 impl MediaWithMessage {
-    pub fn new(media: impl Into<FixedString>, message: Message) -> Self {
+    pub fn new(media: impl Into<FixedString>, kind: Option<MediaKind>, message: Message) -> Self {
         Self {
             media: media.into(),
+            kind,
             message,
+            forwarded_from: None,
+            dimensions: None,
+        }
+    }
+
+    #[must_use]
+    pub fn dimensions(mut self, dimensions: Option<(u32, u32)>) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    #[must_use]
+    pub fn forwarded_from(mut self, original: Option<OriginalMessage>) -> Self {
+        self.forwarded_from = original;
+        self
+    }
+}
+
+/// Points to the original message of a forwarded message
+#[derive(Debug, Clone, Copy)]
+pub struct OriginalMessage {
+    pub guild_id: Option<GuildId>,
+    pub channel_id: GenericChannelId,
+    pub message_id: MessageId,
+}
+
+impl OriginalMessage {
+    /// Where `message` was forwarded from. `None` if it is not a forward
+    pub fn from_message(message: &Message) -> Option<Self> {
+        if message.message_snapshots.is_empty() {
+            return None;
+        }
+
+        let reference = message.message_reference.as_ref()?;
+        Some(Self {
+            guild_id: reference.guild_id,
+            channel_id: reference.channel_id,
+            message_id: reference.message_id?,
+        })
+    }
+
+    /// Link to the original message. Same format as [Message::link]
+    pub fn link(&self) -> String {
+        let Self {
+            guild_id,
+            channel_id,
+            message_id,
+        } = self;
+
+        match guild_id {
+            Some(guild_id) => format!("https://discord.com/channels/{guild_id}/{channel_id}/{message_id}"),
+            None => format!("https://discord.com/channels/@me/{channel_id}/{message_id}"),
         }
     }
 }
@@ -30,79 +91,224 @@ impl Display for MediaWithMessage {
             author, timestamp, ..
         } = &self.message;
 
-        writeln!(
-            f,
-            "[{} {}] {}, {}",
-            timestamp,
-            author.name,
-            self.message.link(),
-            self.media
-        )
+        write!(f, "[{} {}] {}, ", timestamp, author.name, self.message.link())?;
+
+        if let Some(original) = &self.forwarded_from {
+            write!(f, "forwarded from {}, ", original.link())?;
+        }
+
+        writeln!(f, "{}", self.media)
     }
 }
 
 /// Creates a stream of media in a channel
+///
+/// Stops after `limit` messages or at the first message older than `not_before`,
+/// a unix timestamp in seconds, whichever comes first.
 #[instrument(skip_all)]
 pub fn find_media_in_channel<'a>(
     http: &'a impl CacheHttp,
     channel_id: &GenericChannelId,
     limit: usize,
+    not_before: Option<u64>,
 ) -> impl Stream<Item = Result<MediaWithMessage, Error>> + 'a {
-    let stream =
-        futures_stream::StreamExt::then(channel_id.messages_iter(http).take(limit), |message| async move {
-            let mut result = vec![];
-
-            let message = match message {
-                Ok(message) => message,
-                Err(err) => {
-                    tracing::error!("fetching message: {err:?}");
-                    result.push(Err(err));
-                    return futures_stream::iter(result);
-                }
-            };
-
-            for embed in &message.embeds {
-                // only use embeds, don't use thumbnails
-                // this is done to avoid requests to 3rd parties
-                match (&embed.image, &embed.thumbnail) {
-                    (None, _) => {}
-                    (Some(img), _) => {
-                        // tracing::trace!("{} - {} EMBED", message.link(), img.url);
-                        result.push(Ok(MediaWithMessage::new(img.url.clone(), message.clone())));
-                    }
-                }
-            }
+    let messages = recent_messages(http, channel_id, limit, not_before);
+    let stream = futures_stream::StreamExt::then(messages, |message| async move {
+        let mut result = vec![];
 
-            for attachment in &message.attachments {
-                if attachment.content_type.as_ref().is_some_and(media_type_is_image) {
-                    // tracing::trace!("{} - {} ATTACHMENT", message.link(), attachment.url);
-                    result.push(Ok(MediaWithMessage::new(attachment.url.clone(), message.clone())));
-                }
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => {
+                tracing::error!("fetching message: {err:?}");
+                result.push(Err(err));
+                return futures_stream::iter(result);
             }
+        };
+
+        result.extend(media_in_message(&message).into_iter().map(Ok));
 
-            futures_stream::iter(result)
-        });
+        futures_stream::iter(result)
+    });
     futures_stream::StreamExt::flatten(stream)
 }
 
+/// Collect all images of a message, including the ones in forwarded messages.
+///
+/// The order is stable, so the n-th entry can be found again after fetching the message a second time.
+pub fn media_in_message(message: &Message) -> Vec<MediaWithMessage> {
+    let mut result: Vec<MediaWithMessage> = media_in(&message.attachments, &message.embeds)
+        .into_iter()
+        .map(|(media, kind, dimensions)| {
+            MediaWithMessage::new(media, kind, message.clone()).dimensions(dimensions)
+        })
+        .collect();
+
+    // forwarded messages don't have attachments or embeds of their own.
+    // everything lives in the snapshots of the original message
+    let forwarded_from = OriginalMessage::from_message(message);
+    for snapshot in &message.message_snapshots {
+        for (media, kind, dimensions) in media_in(&snapshot.message.attachments, &snapshot.message.embeds) {
+            let media = MediaWithMessage::new(media, kind, message.clone()).dimensions(dimensions);
+            result.push(media.forwarded_from(forwarded_from));
+        }
+    }
+
+    result
+}
+
+/// Collect all images out of attachments and embeds
+fn media_in(
+    attachments: &[Attachment],
+    embeds: &[Embed],
+) -> Vec<(FixedString, Option<MediaKind>, Option<(u32, u32)>)> {
+    let mut result = vec![];
+
+    for embed in embeds {
+        // only use embeds, don't use thumbnails
+        // this is done to avoid requests to 3rd parties
+        match (&embed.image, &embed.thumbnail) {
+            (None, _) => {}
+            (Some(img), _) => {
+                // embeds don't come with a content type, so guess by the url
+                let kind = Url::parse(&img.url).ok().as_ref().and_then(MediaKind::from_url);
+                let dimensions = img
+                    .width
+                    .zip(img.height)
+                    .map(|(w, h)| (u32::from(w), u32::from(h)));
+                result.push((img.url.clone(), kind, dimensions));
+            }
+        }
+    }
+
+    for attachment in attachments {
+        if let Some(content_type) = attachment
+            .content_type
+            .as_ref()
+            .filter(|t| media_type_is_image(t))
+        {
+            let kind = MediaKind::from_content_type(content_type);
+            let dimensions = attachment
+                .width
+                .zip(attachment.height)
+                .map(|(w, h)| (u32::from(w), u32::from(h)));
+            result.push((attachment.url.clone(), kind, dimensions));
+        }
+    }
+
+    result
+}
+
+/// Resolve the channels to look for media in, together with how many messages to look at in each.
+///
+/// Forum channels don't have messages of their own, every post is a thread.
+/// For those the newest threads are used instead, capped by the scheduler settings.
+#[instrument(skip_all)]
+pub async fn media_source_channels(
+    cache_http: &impl CacheHttp,
+    channel_id: GenericChannelId,
+    limit: usize,
+) -> Result<Vec<(GenericChannelId, usize)>, Error> {
+    let http = cache_http.http();
+
+    let Channel::Guild(channel) = http.get_channel(channel_id).await? else {
+        return Ok(vec![(channel_id, limit)]);
+    };
+
+    if !matches!(channel.base.kind, ChannelType::Forum | ChannelType::Media) {
+        return Ok(vec![(channel_id, limit)]);
+    }
+
+    let settings = &Settings::get().scheduler;
+    let maximum_threads = settings.maximum_forum_threads;
+    let limit_per_thread = limit.min(settings.maximum_messages_per_thread);
+
+    let forum_id = ChannelId::new(channel_id.get());
+
+    // active threads can only be fetched for the whole guild
+    let mut threads: Vec<ThreadId> = channel
+        .base
+        .guild_id
+        .get_active_threads(http)
+        .await?
+        .threads
+        .iter()
+        .filter(|thread| thread.parent_id == forum_id)
+        .map(|thread| thread.id)
+        .collect();
+    // newest posts first
+    threads.sort_unstable_by(|a, b| b.cmp(a));
+
+    // archived threads are returned newest first, page by page
+    let mut before = None;
+    while threads.len() < maximum_threads {
+        let archived = forum_id
+            .get_archived_public_threads(http, before, Some(100))
+            .await?;
+        threads.extend(archived.threads.iter().map(|thread| thread.id));
+
+        before = archived
+            .threads
+            .last()
+            .and_then(|thread| thread.thread_metadata.archive_timestamp);
+
+        if !archived.has_more || before.is_none() {
+            break;
+        }
+    }
+
+    threads.truncate(maximum_threads);
+    debug!("Forum {channel_id} resolved to {} threads", threads.len());
+
+    Ok(threads
+        .into_iter()
+        .map(|thread| (thread.widen(), limit_per_thread))
+        .collect())
+}
+
+/// Messages in a channel, newest first.
+///
+/// Stops after `limit` messages or at the first message older than `not_before`,
+/// a unix timestamp in seconds, whichever comes first.
+pub fn recent_messages<'a>(
+    http: &'a impl CacheHttp,
+    channel_id: &GenericChannelId,
+    limit: usize,
+    not_before: Option<u64>,
+) -> impl Stream<Item = Result<Message, Error>> + 'a {
+    let not_before = not_before.map_or(i64::MIN, |timestamp| i64::try_from(timestamp).unwrap_or(i64::MAX));
+
+    channel_id
+        .messages_iter(http)
+        .take(limit)
+        .take_while(move |message| match message {
+            Ok(message) => message.timestamp.unix_timestamp() >= not_before,
+            // let the error through so it can be handled
+            Err(_) => true,
+        })
+}
+
 /// Return the last message the bot is gonna look at for that schedule
 #[instrument(skip_all)]
 pub async fn last_reachable_message(http: &impl CacheHttp, schedule: &Schedule) -> Option<Message> {
     let limit = schedule.message_limit().map(NonZeroU32::get).unwrap_or_default();
 
-    let messages: Vec<Message> = schedule
-        .channel_id()
-        .messages_iter(http)
-        .take(limit as usize)
-        .filter_map(Result::ok)
-        .collect()
-        .await;
+    let messages: Vec<Message> = recent_messages(
+        http,
+        &schedule.channel_id(),
+        limit as usize,
+        schedule.lookback_cutoff(),
+    )
+    .filter_map(Result::ok)
+    .collect()
+    .await;
     messages.last().cloned()
 }
 
 pub fn media_type_is_image(media_type: impl AsRef<str>) -> bool {
-    matches!(
-        media_type.as_ref().to_lowercase().as_str(),
-        "image/png" | "image/jpg" | "image/jpeg" | "image/gif"
-    )
+    match media_type.as_ref().to_lowercase().as_str() {
+        "image/png" | "image/jpg" | "image/jpeg" | "image/gif" | "image/webp" => true,
+        // decoding avif needs a system library, so it's behind a feature
+        "image/avif" => cfg!(feature = "avif"),
+        _ => false,
+    }
 }
@@ -0,0 +1,488 @@
+//! Turning a downloaded image into a banner
+//!
+//! Images are decoded locally, cropped to the aspect ratio of the banner and resized.
+//! This works the same for every url, not just the ones on the discord cdn.
+
+use std::{io::Cursor, time::Duration};
+
+use image::{
+    AnimationDecoder, Delay, DynamicImage, Frame, ImageError, ImageFormat, RgbaImage,
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        jpeg::JpegEncoder,
+        webp::WebPDecoder,
+    },
+    error::{DecodingError, ImageFormatHint},
+    imageops::{self, FilterType},
+};
+use tracing::debug;
+
+use crate::{Settings, caption::Caption, collage::CollageLayout, crop_hint::CropHint, media_kind::MediaKind};
+
+/// Quality used when encoding jpegs
+const JPEG_QUALITY: u8 = 90;
+
+/// Jpeg qualities to try when an image is too big
+const JPEG_QUALITY_STEPS: [u8; 4] = [80, 70, 60, 50];
+
+/// How often a static image is scaled down when lowering the quality is not enough
+const MAXIMUM_DOWNSCALES: usize = 4;
+
+/// How fast gifs are encoded. 1 is best quality, 30 is fastest
+const GIF_SPEED: i32 = 10;
+
+/// What to try, in order, when a gif is too big
+const GIF_SHRINK_STEPS: [GifShrink; 6] = [
+    GifShrink::DropFrames,
+    GifShrink::ReduceColors(5),
+    GifShrink::Downscale,
+    GifShrink::DropFrames,
+    GifShrink::ReduceColors(4),
+    GifShrink::Downscale,
+];
+
+/// Ways of making a gif smaller
+#[derive(Debug, Clone, Copy)]
+enum GifShrink {
+    /// Drop every other frame, showing the remaining ones for longer
+    DropFrames,
+    /// Only keep this many bits per color channel, so the palette needs fewer colors
+    ReduceColors(u8),
+    /// Scale every frame down to three quarters of its size
+    Downscale,
+}
+
+/// Size of the final banner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BannerSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BannerSize {
+    /// The banner size from the settings
+    pub fn from_settings() -> Self {
+        let settings = &Settings::get().image;
+        Self {
+            width: settings.width.max(1),
+            height: settings.height.max(1),
+        }
+    }
+}
+
+/// How to turn an image into a banner
+#[derive(Debug, Clone)]
+pub struct ProcessOptions {
+    /// Size of the banner
+    pub size: BannerSize,
+    /// Compress harder until the result is smaller than this many bytes
+    pub max_bytes: usize,
+    /// Turn animated images into a static one, using this frame. 0 is the first frame
+    pub still_frame: Option<usize>,
+    /// Text drawn onto the banner
+    pub caption: Option<Caption>,
+    /// Which part of the image to keep when cropping
+    pub crop_hint: CropHint,
+}
+
+impl ProcessOptions {
+    pub fn new(size: BannerSize, max_bytes: usize) -> Self {
+        Self {
+            size,
+            max_bytes,
+            still_frame: None,
+            caption: None,
+            crop_hint: CropHint::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn still_frame(mut self, still_frame: Option<usize>) -> Self {
+        self.still_frame = still_frame;
+        self
+    }
+
+    #[must_use]
+    pub fn caption(mut self, caption: Option<Caption>) -> Self {
+        self.caption = caption;
+        self
+    }
+
+    #[must_use]
+    pub fn crop_hint(mut self, crop_hint: Option<CropHint>) -> Self {
+        self.crop_hint = crop_hint.unwrap_or_default();
+        self
+    }
+}
+
+/// An image that went through the pipeline, ready to be uploaded
+#[derive(Debug)]
+pub struct ProcessedImage {
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+}
+
+/// Crop an image to the aspect ratio of the banner and scale it down to it.
+///
+/// The crop follows the [CropHint], which is the center by default.
+/// Images smaller than the banner are only cropped, never scaled up.
+/// Animated images become gifs, everything else becomes a jpeg or png. Discord does not take anything else.
+/// If the result is bigger than `max_bytes`, it is compressed harder and scaled down further.
+/// The returned image can still be too big, if nothing helped.
+///
+/// This is cpu heavy, don't call it on the async runtime.
+pub fn process(
+    bytes: &[u8],
+    format: ImageFormat,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    match (is_animated(bytes, format), options.still_frame) {
+        (true, Some(still_frame)) => process_still_frame(bytes, format, still_frame, options),
+        (true, None) => process_animated(bytes, format, options),
+        (false, _) => process_static(bytes, format, options),
+    }
+}
+
+fn process_static(
+    bytes: &[u8],
+    format: ImageFormat,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    let image = image::load_from_memory_with_format(bytes, format)?;
+    fit_static(image, format, options)
+}
+
+/// Use a single frame of an animated image
+fn process_still_frame(
+    bytes: &[u8],
+    format: ImageFormat,
+    still_frame: usize,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    let image = decode_still(bytes, format, still_frame)?;
+    fit_static(image, ImageFormat::Png, options)
+}
+
+/// Fully decode an image into a single picture
+///
+/// Animated images use the frame at index `frame`. Frames past the end mean the last frame.
+pub fn decode_still(bytes: &[u8], format: ImageFormat, frame: usize) -> Result<DynamicImage, ImageError> {
+    if !is_animated(bytes, format) {
+        return image::load_from_memory_with_format(bytes, format);
+    }
+
+    let mut frames = decode_frames(bytes, format)?;
+    let frame = if frame < frames.len() {
+        frames.swap_remove(frame)
+    } else {
+        frames.pop().expect("decode_frames never returns an empty list")
+    };
+
+    Ok(DynamicImage::ImageRgba8(frame.into_buffer()))
+}
+
+/// Tile already decoded images into a single banner, following the `layout`
+///
+/// Each image is cropped to fill its tile, following its own [CropHint]. Missing images leave their tile empty.
+/// The result is always a static image.
+pub fn process_collage(
+    images: Vec<(DynamicImage, CropHint)>,
+    layout: CollageLayout,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    let mut canvas = RgbaImage::new(options.size.width, options.size.height);
+
+    for ((image, crop_hint), tile) in images.into_iter().zip(layout.tiles(options.size)) {
+        let tile_size = BannerSize {
+            width: tile.width,
+            height: tile.height,
+        };
+        let (x, y, width, height) = crop_rect(image.width(), image.height(), tile_size, crop_hint);
+        let resized =
+            image
+                .crop_imm(x, y, width, height)
+                .resize_exact(tile.width, tile.height, FilterType::Lanczos3);
+        imageops::replace(&mut canvas, &resized.to_rgba8(), tile.x.into(), tile.y.into());
+    }
+
+    finish_static(DynamicImage::ImageRgba8(canvas), ImageFormat::Jpeg, options)
+}
+
+fn fit_static(
+    image: DynamicImage,
+    format: ImageFormat,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    let (x, y, width, height) = crop_rect(image.width(), image.height(), options.size, options.crop_hint);
+    let (target_width, target_height) = scaled_dimensions(width, height, options.size);
+
+    let image =
+        image
+            .crop_imm(x, y, width, height)
+            .resize_exact(target_width, target_height, FilterType::Lanczos3);
+
+    finish_static(image, format, options)
+}
+
+/// Draw the caption, encode and shrink if needed. The image already has its final size
+fn finish_static(
+    mut image: DynamicImage,
+    format: ImageFormat,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    if let Some(caption) = &options.caption {
+        let mut captioned = image.to_rgba8();
+        caption.draw(&mut captioned, caption_scale(image.width(), options.size));
+        image = DynamicImage::ImageRgba8(captioned);
+    }
+
+    let processed = encode_static(&image, format)?;
+    if processed.bytes.len() < options.max_bytes {
+        return Ok(processed);
+    }
+
+    shrink_static(image, processed, options.max_bytes)
+}
+
+/// Lower the jpeg quality step by step. If that's not enough, scale down and try again
+fn shrink_static(
+    mut image: DynamicImage,
+    mut processed: ProcessedImage,
+    max_bytes: usize,
+) -> Result<ProcessedImage, ImageError> {
+    for _ in 0..=MAXIMUM_DOWNSCALES {
+        for quality in JPEG_QUALITY_STEPS {
+            processed = encode_jpeg(&image, quality)?;
+            debug!(
+                "Shrinking image: {}x{} at quality {quality} is {} bytes",
+                image.width(),
+                image.height(),
+                processed.bytes.len()
+            );
+
+            if processed.bytes.len() < max_bytes {
+                return Ok(processed);
+            }
+        }
+
+        let (width, height) = ((image.width() * 3 / 4).max(1), (image.height() * 3 / 4).max(1));
+        image = image.resize(width, height, FilterType::Lanczos3);
+    }
+
+    Ok(processed)
+}
+
+fn process_animated(
+    bytes: &[u8],
+    format: ImageFormat,
+    options: ProcessOptions,
+) -> Result<ProcessedImage, ImageError> {
+    let frames = decode_frames(bytes, format)?;
+
+    // frames coming out of the decoders are already composited, so they all have the full size
+    let first = frames[0].buffer();
+    let (x, y, width, height) = crop_rect(first.width(), first.height(), options.size, options.crop_hint);
+    let (target_width, target_height) = scaled_dimensions(width, height, options.size);
+
+    let mut frames: Vec<Frame> = frames
+        .into_iter()
+        .map(|frame| {
+            let delay = frame.delay();
+            let cropped = imageops::crop_imm(frame.buffer(), x, y, width, height).to_image();
+            let mut resized = imageops::resize(&cropped, target_width, target_height, FilterType::Lanczos3);
+            if let Some(caption) = &options.caption {
+                caption.draw(&mut resized, caption_scale(target_width, options.size));
+            }
+            Frame::from_parts(resized, 0, 0, delay)
+        })
+        .collect();
+
+    let mut processed = encode_gif(&frames)?;
+
+    for step in GIF_SHRINK_STEPS {
+        if processed.bytes.len() < options.max_bytes {
+            break;
+        }
+
+        frames = shrink_gif(frames, step);
+        processed = encode_gif(&frames)?;
+        debug!(
+            "Shrinking gif: {step:?} left {} frames with {} bytes",
+            frames.len(),
+            processed.bytes.len()
+        );
+    }
+
+    Ok(processed)
+}
+
+/// Gifs are always treated as animated, even with a single frame
+fn is_animated(bytes: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Gif => true,
+        ImageFormat::WebP => MediaKind::sniff(bytes) == Some(MediaKind::Animated),
+        _ => false,
+    }
+}
+
+/// All frames of an animated gif or webp. Never empty
+fn decode_frames(bytes: &[u8], format: ImageFormat) -> Result<Vec<Frame>, ImageError> {
+    let frames = match format {
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(bytes))?
+            .into_frames()
+            .collect_frames()?,
+        _ => GifDecoder::new(Cursor::new(bytes))?
+            .into_frames()
+            .collect_frames()?,
+    };
+
+    if frames.is_empty() {
+        return Err(ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(format),
+            "animation has no frames",
+        )));
+    }
+
+    Ok(frames)
+}
+
+fn shrink_gif(frames: Vec<Frame>, step: GifShrink) -> Vec<Frame> {
+    match step {
+        GifShrink::DropFrames if frames.len() > 1 => frames
+            .chunks(2)
+            .map(|pair| {
+                // keep the timing intact by showing the kept frame for as long as both together
+                let delay: Duration = pair.iter().map(|frame| Duration::from(frame.delay())).sum();
+                Frame::from_parts(
+                    pair[0].buffer().clone(),
+                    0,
+                    0,
+                    Delay::from_saturating_duration(delay),
+                )
+            })
+            .collect(),
+        GifShrink::DropFrames => frames,
+        GifShrink::ReduceColors(bits) => {
+            let mask = u8::MAX << (8 - bits.clamp(1, 8));
+            frames
+                .into_iter()
+                .map(|frame| {
+                    let delay = frame.delay();
+                    let mut buffer = frame.into_buffer();
+                    for pixel in buffer.pixels_mut() {
+                        for channel in &mut pixel.0[..3] {
+                            *channel &= mask;
+                        }
+                    }
+                    Frame::from_parts(buffer, 0, 0, delay)
+                })
+                .collect()
+        }
+        GifShrink::Downscale => frames
+            .into_iter()
+            .map(|frame| {
+                let delay = frame.delay();
+                let buffer = frame.buffer();
+                let (width, height) = ((buffer.width() * 3 / 4).max(1), (buffer.height() * 3 / 4).max(1));
+                let resized = imageops::resize(buffer, width, height, FilterType::Lanczos3);
+                Frame::from_parts(resized, 0, 0, delay)
+            })
+            .collect(),
+    }
+}
+
+fn encode_gif(frames: &[Frame]) -> Result<ProcessedImage, ImageError> {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut bytes, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames.iter().cloned())?;
+    }
+
+    Ok(ProcessedImage {
+        bytes,
+        format: ImageFormat::Gif,
+    })
+}
+
+/// Jpegs stay jpegs, everything else becomes a png
+fn encode_static(image: &DynamicImage, format: ImageFormat) -> Result<ProcessedImage, ImageError> {
+    match format {
+        ImageFormat::Jpeg => encode_jpeg(image, JPEG_QUALITY),
+        _ => {
+            let mut bytes = Vec::new();
+            image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+            Ok(ProcessedImage {
+                bytes,
+                format: ImageFormat::Png,
+            })
+        }
+    }
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<ProcessedImage, ImageError> {
+    let mut bytes = Vec::new();
+
+    // jpegs can't have an alpha channel
+    let image = DynamicImage::ImageRgb8(image.to_rgb8());
+    image.write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality))?;
+
+    Ok(ProcessedImage {
+        bytes,
+        format: ImageFormat::Jpeg,
+    })
+}
+
+/// The biggest rectangle with the aspect ratio of `size`, placed according to the hint.
+/// Returns `(x, y, width, height)`
+fn crop_rect(width: u32, height: u32, size: BannerSize, hint: CropHint) -> (u32, u32, u32, u32) {
+    let (focus_x, focus_y) = hint.focus();
+
+    // compare width / height against size.width / size.height without floats
+    let (width_64, height_64) = (u64::from(width), u64::from(height));
+    let (target_width, target_height) = (u64::from(size.width), u64::from(size.height));
+
+    if width_64 * target_height > height_64 * target_width {
+        // too wide, cut off left and right
+        let cropped_width = u32::try_from(height_64 * target_width / target_height)
+            .unwrap_or(width)
+            .max(1);
+        (
+            crop_offset(width, cropped_width, focus_x),
+            0,
+            cropped_width,
+            height,
+        )
+    } else {
+        // too tall, cut off top and bottom
+        let cropped_height = u32::try_from(width_64 * target_height / target_width)
+            .unwrap_or(height)
+            .max(1);
+        (
+            0,
+            crop_offset(height, cropped_height, focus_y),
+            width,
+            cropped_height,
+        )
+    }
+}
+
+/// Where a window of `length` starts inside of `full`, so it is centered on `focus` as far as possible
+fn crop_offset(full: u32, length: u32, focus: f32) -> u32 {
+    let start = full as f32 * focus.clamp(0.0, 1.0) - length as f32 / 2.0;
+    (start.round().max(0.0) as u32).min(full.saturating_sub(length))
+}
+
+/// Scale down to `size`, but never up
+fn scaled_dimensions(width: u32, height: u32, size: BannerSize) -> (u32, u32) {
+    if width > size.width {
+        (size.width, size.height)
+    } else {
+        (width, height)
+    }
+}
+
+/// How big an image of this width is compared to the full banner
+fn caption_scale(width: u32, size: BannerSize) -> f32 {
+    width as f32 / size.width as f32
+}
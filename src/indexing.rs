@@ -0,0 +1,407 @@
+//! Keeping the media index of source channels up to date
+//!
+//! A channel is backfilled once by scanning its history, page by page.
+//! The progress is saved after every page, so a backfill that runs out of time continues on the next run.
+//! Message events from the gateway keep the index current, from the moment the backfill starts.
+//! Messages posted while the bot was offline are caught up on after connecting.
+//! Changes to arrow reactions update the crop hint, see [crate::crop_hint].
+//! See [crate::database::media_index] for how it is stored.
+
+use std::{collections::HashSet, sync::Arc};
+
+use poise::serenity_prelude::{
+    CacheHttp, Error as SerenityError, GenericChannelId, GetMessages, GuildId, Http,
+    HttpError as SerenityHttpError, Message, MessageId,
+};
+use reqwest::{Client, StatusCode};
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{
+    Error, Settings,
+    constants::DISCORD_EPOCH,
+    crop_hint::CropHint,
+    database::{Database, media_index::IndexedMedia},
+    deduplication::{hash_in_background, media_identity},
+    finding_media::{MediaWithMessage, media_in_message},
+    utils::current_unix_timestamp,
+};
+
+/// Messages fetched at once while backfilling. The most discord allows
+const BACKFILL_PAGE_SIZE: u64 = 100;
+
+/// Backfill the index of a channel, if it did not look back at least `depth` messages yet.
+/// `depth` is capped by how far any schedule could look back.
+///
+/// Stops early at messages older than `not_before`, a unix timestamp in seconds.
+/// A later call without that cutoff continues from there.
+///
+/// `guild_id` is needed to tell whether an arrow reaction came from a moderator, see [CropHint::from_message].
+#[instrument(skip_all)]
+pub async fn ensure_indexed(
+    http: &impl CacheHttp,
+    db: &Database,
+    reqw_client: &Client,
+    guild_id: GuildId,
+    channel_id: GenericChannelId,
+    depth: usize,
+    not_before: Option<u64>,
+) -> Result<(), Error> {
+    let channel = channel_id.get();
+    let not_before = not_before.map_or(i64::MIN, |timestamp| i64::try_from(timestamp).unwrap_or(i64::MAX));
+    let depth = depth.min(maximum_depth()) as u64;
+
+    let indexed_depth = db.media_index_depth(channel).await?;
+    if indexed_depth.is_some_and(|indexed_depth| indexed_depth >= depth) {
+        return Ok(());
+    }
+
+    let (mut indexed_depth, mut cursor) = match (indexed_depth, db.media_index_cursor(channel).await?) {
+        (Some(indexed_depth), Some(cursor)) => (indexed_depth, MessageId::new(cursor)),
+        (indexed_depth, _) => {
+            if indexed_depth.is_some() {
+                // indexed before backfills could be continued. there is no telling where it stopped
+                db.drop_media_index(channel).await?;
+            }
+
+            // messages posted from now on are indexed by gateway events, the backfill reads everything older
+            let now = (current_unix_timestamp() * 1000).saturating_sub(DISCORD_EPOCH) << 22;
+            db.set_media_index_progress(channel, 0, now).await?;
+            db.set_media_index_head(channel, now).await?;
+            (0, MessageId::new(now))
+        }
+    };
+
+    // everything before the cursor is older than the cursor itself
+    let within_cutoff = |cursor: MessageId| cursor.created_at().unix_timestamp() >= not_before;
+    if !within_cutoff(cursor) {
+        return Ok(());
+    }
+
+    info!("Backfilling media index of {channel_id}, looking at messages {indexed_depth} to {depth}");
+
+    while indexed_depth < depth && within_cutoff(cursor) {
+        let page_size = (depth - indexed_depth).min(BACKFILL_PAGE_SIZE) as u8;
+        let messages = channel_id
+            .messages(http.http(), GetMessages::new().before(cursor).limit(page_size))
+            .await?;
+
+        match messages.last() {
+            Some(oldest) => cursor = oldest.id,
+            // the history ended. looking further back later won't find anything either
+            None => indexed_depth = depth,
+        }
+
+        let mut indexed = Vec::new();
+        for message in &messages {
+            // newest message when the backfill started is at position 0, older ones go into the negatives.
+            // new messages coming in count upwards from there
+            let position = -i64::try_from(indexed_depth).unwrap_or(i64::MAX);
+            let crop_hint = crop_hint_of_existing(http.http(), Some(guild_id), message).await;
+            indexed.extend(index_message(db, message, position, crop_hint).await?);
+            indexed_depth += 1;
+        }
+        hash_in_background(db.clone(), reqw_client.clone(), indexed);
+
+        db.set_media_index_progress(channel, indexed_depth, cursor.get())
+            .await?;
+    }
+
+    debug!("Backfilled {indexed_depth} messages of {channel_id}");
+    Ok(())
+}
+
+/// Index the messages of all indexed channels that were posted while the bot was offline.
+/// Gateway events only cover the time the bot is connected
+pub fn catch_up_in_background(http: Arc<Http>, db: Database, reqw_client: Client) {
+    tokio::spawn(async move {
+        let channels = match db.indexed_channels().await {
+            Ok(channels) => channels,
+            Err(err) => {
+                error!("Could not look up indexed channels: {err}");
+                return;
+            }
+        };
+
+        for channel in channels {
+            let channel_id = GenericChannelId::new(channel);
+            if let Err(err) = catch_up(&http, &db, &reqw_client, channel_id).await {
+                warn!("Could not catch up on the media index of {channel_id}: {err}");
+            }
+        }
+    });
+}
+
+/// Index the messages after the newest indexed one, oldest first
+#[instrument(skip_all)]
+async fn catch_up(
+    http: &Http,
+    db: &Database,
+    reqw_client: &Client,
+    channel_id: GenericChannelId,
+) -> Result<(), Error> {
+    let channel = channel_id.get();
+    let Some(mut head) = db.media_index_head(channel).await? else {
+        return Ok(());
+    };
+
+    let mut caught_up = 0;
+    loop {
+        let mut messages = channel_id
+            .messages(
+                http,
+                GetMessages::new()
+                    .after(MessageId::new(head))
+                    .limit(BACKFILL_PAGE_SIZE as u8),
+            )
+            .await?;
+        if messages.is_empty() {
+            break;
+        }
+
+        caught_up += messages.len();
+        if caught_up > maximum_depth() {
+            // so much was missed that none of the index is in reach anymore. backfill from scratch instead
+            info!("Missed too many messages in {channel_id}, dropping its media index");
+            db.drop_media_index(channel).await?;
+            return Ok(());
+        }
+
+        // positions count upwards, so go from old to new
+        messages.sort_by_key(|message| message.id);
+
+        let mut indexed = Vec::new();
+        for message in &messages {
+            let position = db.next_media_index_position(channel).await?;
+            // messages from the api usually don't say which guild they are in. then only the poster's arrows
+            // count, until the reactions change
+            let crop_hint = crop_hint_of_existing(http, message.guild_id, message).await;
+            indexed.extend(index_message(db, message, position, crop_hint).await?);
+            head = message.id.get();
+        }
+        hash_in_background(db.clone(), reqw_client.clone(), indexed);
+
+        db.set_media_index_head(channel, head).await?;
+
+        if messages.len() < BACKFILL_PAGE_SIZE as usize {
+            break;
+        }
+    }
+
+    if caught_up > 0 {
+        debug!("Caught up on {caught_up} messages in {channel_id}");
+    }
+    Ok(())
+}
+
+/// All indexed media of a channel within the last `limit` messages
+/// and newer than `not_before`, a unix timestamp in seconds.
+///
+/// Also drops entries that are too old to be reached by any schedule.
+#[instrument(skip_all)]
+pub async fn media_in_reach(
+    db: &Database,
+    channel_id: GenericChannelId,
+    limit: usize,
+    not_before: Option<u64>,
+) -> Result<Vec<IndexedMedia>, Error> {
+    let keep = i64::try_from(maximum_depth()).unwrap_or(i64::MAX);
+    let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+    let not_before = not_before.map_or(i64::MIN, |timestamp| i64::try_from(timestamp).unwrap_or(i64::MAX));
+
+    let newest = db.media_index_position(channel_id.get()).await?;
+    let (entries, outdated): (Vec<_>, Vec<_>) = db
+        .indexed_media(channel_id.get())
+        .await?
+        .into_iter()
+        .partition(|entry| newest - entry.position < keep);
+
+    if !outdated.is_empty() {
+        debug!(
+            "Dropping {} outdated entries from the index of {channel_id}",
+            outdated.len()
+        );
+        db.remove_indexed_media(channel_id.get(), &outdated).await?;
+    }
+
+    // hashes of images that were deleted, edited away or are too old are no use anymore
+    let identities: HashSet<String> = entries.iter().map(|entry| media_identity(&entry.url)).collect();
+    db.prune_image_hashes(channel_id.get(), &identities).await?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| newest - entry.position < limit)
+        .filter(|entry| MessageId::new(entry.message_id).created_at().unix_timestamp() >= not_before)
+        .collect())
+}
+
+/// Fetch the message of an indexed media again.
+/// Discord attachment urls expire after a while, so the url in the index can't be used directly.
+///
+/// Returns `None` if the message or the media in it is gone. The index is updated accordingly.
+#[instrument(skip_all)]
+pub async fn resolve_indexed_media(
+    http: &impl CacheHttp,
+    db: &Database,
+    reqw_client: &Client,
+    entry: &IndexedMedia,
+) -> Result<Option<MediaWithMessage>, Error> {
+    let channel_id = GenericChannelId::new(entry.channel_id);
+
+    let message = match channel_id.message(http, MessageId::new(entry.message_id)).await {
+        Ok(message) => message,
+        Err(SerenityError::Http(SerenityHttpError::UnsuccessfulRequest(error_response)))
+            if error_response.status_code == StatusCode::NOT_FOUND =>
+        {
+            debug!("Indexed message {} is gone", entry.message_id);
+            db.remove_indexed_messages(entry.channel_id, &[entry.message_id])
+                .await?;
+            return Ok(None);
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let media = media_in_message(&message).into_iter().nth(entry.nth);
+    if media.is_none() {
+        // the message was edited and we missed it
+        reindex_message(db, reqw_client, &message).await?;
+    }
+
+    Ok(media)
+}
+
+/// Add a newly posted message to the index, if the channel is indexed
+#[instrument(skip_all)]
+pub async fn index_new_message(db: &Database, reqw_client: &Client, message: &Message) -> Result<(), Error> {
+    let channel_id = message.channel_id.get();
+    if db.media_index_depth(channel_id).await?.is_none() {
+        return Ok(());
+    }
+
+    // already indexed while catching up
+    let head = db.media_index_head(channel_id).await?;
+    if head.is_some_and(|head| message.id.get() <= head) {
+        return Ok(());
+    }
+
+    // count every message, not just the ones with media. message limits depend on it
+    let position = db.next_media_index_position(channel_id).await?;
+    // nobody reacted yet
+    let crop_hint = CropHint::from_content(&message.content);
+    let indexed = index_message(db, message, position, crop_hint).await?;
+    db.set_media_index_head(channel_id, message.id.get()).await?;
+    hash_in_background(db.clone(), reqw_client.clone(), indexed);
+    Ok(())
+}
+
+/// Update an edited message in the index, if the channel is indexed
+///
+/// Edits are how embeds show up after a link was posted, so this is not just for typos.
+#[instrument(skip_all)]
+pub async fn reindex_message(db: &Database, reqw_client: &Client, message: &Message) -> Result<(), Error> {
+    let channel_id = message.channel_id.get();
+    if db.media_index_depth(channel_id).await?.is_none() {
+        return Ok(());
+    }
+
+    let previous = db.indexed_media_of_message(channel_id, message.id.get()).await?;
+    let position = match previous.first() {
+        Some(entry) => entry.position,
+        // the message had no media until now. it's probably a recent one
+        None => db.media_index_position(channel_id).await?,
+    };
+
+    // edits don't touch reactions, so a hint from them stays
+    let crop_hint =
+        CropHint::from_content(&message.content).or(previous.first().and_then(|entry| entry.crop_hint));
+
+    db.remove_indexed_media(channel_id, &previous).await?;
+    let indexed = index_message(db, message, position, crop_hint).await?;
+    hash_in_background(db.clone(), reqw_client.clone(), indexed);
+    Ok(())
+}
+
+/// Update the crop hint of a message after its arrow reactions changed, if it has indexed media
+///
+/// Reaction events don't contain the message, so it is fetched again.
+#[instrument(skip_all)]
+pub async fn update_crop_hint(
+    http: &impl CacheHttp,
+    db: &Database,
+    guild_id: Option<GuildId>,
+    channel_id: GenericChannelId,
+    message_id: MessageId,
+) -> Result<(), Error> {
+    // most reactions are on messages without images, those don't need a request
+    let entries = db
+        .indexed_media_of_message(channel_id.get(), message_id.get())
+        .await?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let message = channel_id.message(http, message_id).await?;
+    let crop_hint = CropHint::from_message(http.http(), guild_id, &message).await;
+
+    let entries: Vec<IndexedMedia> = entries
+        .into_iter()
+        .map(|entry| IndexedMedia { crop_hint, ..entry })
+        .collect();
+    db.index_media(channel_id.get(), &entries).await?;
+    Ok(())
+}
+
+/// Remove deleted messages from the index, if the channel is indexed
+#[instrument(skip_all)]
+pub async fn unindex_messages(
+    db: &Database,
+    channel_id: GenericChannelId,
+    message_ids: &[MessageId],
+) -> Result<(), Error> {
+    let channel_id = channel_id.get();
+    if db.media_index_depth(channel_id).await?.is_none() {
+        return Ok(());
+    }
+
+    let message_ids: Vec<u64> = message_ids.iter().map(|id| id.get()).collect();
+    db.remove_indexed_messages(channel_id, &message_ids).await?;
+    Ok(())
+}
+
+/// How many messages any schedule could possibly look back
+fn maximum_depth() -> usize {
+    let scheduler = &Settings::get().scheduler;
+    scheduler
+        .maximum_message_limit
+        .max(scheduler.lookback_message_cap) as usize
+}
+
+/// Crop hint of a message that was posted before it was indexed, so it might have reactions already.
+/// Only messages with media are worth the requests for checking who reacted
+async fn crop_hint_of_existing(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    message: &Message,
+) -> Option<CropHint> {
+    if media_in_message(message).is_empty() {
+        return None;
+    }
+
+    CropHint::from_message(http, guild_id, message).await
+}
+
+/// Returns what was indexed
+async fn index_message(
+    db: &Database,
+    message: &Message,
+    position: i64,
+    crop_hint: Option<CropHint>,
+) -> Result<Vec<IndexedMedia>, Error> {
+    let entries: Vec<IndexedMedia> = media_in_message(message)
+        .iter()
+        .enumerate()
+        .map(|(nth, media)| IndexedMedia::new(media, nth, position, crop_hint))
+        .collect();
+
+    db.index_media(message.channel_id.get(), &entries).await?;
+    Ok(entries)
+}
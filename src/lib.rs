@@ -1,19 +1,32 @@
+pub mod announcement;
+pub mod caption;
 pub mod cli;
+pub mod collage;
 pub mod commands;
 pub mod constants;
+pub mod crop_hint;
 pub mod database;
+pub mod deduplication;
 pub mod error;
 pub mod event_handler;
 pub mod finding_media;
+pub mod image_pipeline;
+pub mod indexing;
 pub mod interval;
+pub mod localization;
+pub mod media_kind;
+pub mod notification;
+pub mod owner_digest;
 pub mod schedule;
 pub mod schedule_runner;
+pub mod schedule_target;
 pub mod setting_banner;
 pub mod settings;
 pub mod shutdown;
 pub mod startup;
 pub mod state;
 pub mod utils;
+pub mod webhook;
 
 pub use error::Error;
 pub use settings::Settings;
@@ -0,0 +1,148 @@
+//! English messages. Every other language falls back to these
+
+pub const MESSAGES: &[(&str, &str)] = &[
+    // names of schedule targets, used inside sentences
+    ("target.banner", "banner"),
+    ("target.icon", "icon"),
+    ("target.invite_splash", "invite splash"),
+    ("target.discovery_splash", "discovery splash"),
+    ("target.member_avatar", "bot avatar"),
+    ("target.member_banner", "bot banner"),
+    // errors when running commands
+    ("error.guild_only", "Command must be run in a server"),
+    (
+        "error.guild_has_no_banner_set",
+        "Server doesn't have a banner set",
+    ),
+    (
+        "error.guild_has_no_banner_feature",
+        "Server doesn't have the required boost level",
+    ),
+    (
+        "error.guild_has_no_animated_banner_feature",
+        "Server doesn't have the required boost level for animated banners",
+    ),
+    (
+        "error.guild_has_no_animated_icon_feature",
+        "Server doesn't have the required boost level for animated icons",
+    ),
+    (
+        "error.guild_has_no_invite_splash_feature",
+        "Server doesn't have the required boost level for an invite splash",
+    ),
+    (
+        "error.guild_is_not_discoverable",
+        "Server must be discoverable to have a discovery splash",
+    ),
+    (
+        "error.splash_cannot_be_animated",
+        "Splash images can't be animated. Set a still frame or pick another media filter",
+    ),
+    (
+        "error.below_min_interval",
+        "Interval must be at least {minutes} minutes",
+    ),
+    (
+        "error.above_max_interval",
+        "Interval must be at most {minutes} minutes",
+    ),
+    (
+        "error.message_limit_is_zero",
+        "Message limit must be greater than 0",
+    ),
+    (
+        "error.above_max_message_limit",
+        "Message limit must be at most {limit}",
+    ),
+    ("error.lookback_days_is_zero", "Lookback must be at least 1 day"),
+    (
+        "error.above_max_lookback_days",
+        "Lookback must be at most {days} days",
+    ),
+    (
+        "error.start_time_in_the_past",
+        "Start time cannot be in the past. Now={now}, given={given}",
+    ),
+    // /start, /stop and /current_schedule
+    (
+        "start.scheduled",
+        "Scheduling {target} change for every {interval} minutes using channel {channel}. Starting at {start_at} in {seconds} seconds.",
+    ),
+    ("stop.stopping", "Stopping this schedule:"),
+    ("schedule.none_running", "There is no schedule running"),
+    ("schedule.channel", "Channel: "),
+    ("schedule.message_limit", "Message limit: {limit}."),
+    ("schedule.lookback", "Lookback: {days} days."),
+    ("schedule.media", "Media: {media}."),
+    ("schedule.still_frame", "Still frame: {frame}."),
+    ("schedule.caption", "Caption: {caption}."),
+    ("schedule.collage", "Collage: {collage}."),
+    (
+        "schedule.pool_stats",
+        "Images to choose from: {candidates}. Duplicates skipped: {duplicates}.",
+    ),
+    ("schedule.last_reachable_message", "Last reachable message: "),
+    ("schedule.click_here", "click here"),
+    // /help
+    (
+        "help.join",
+        "If you need help, join: https://discord.gg/MMJFtCtYPP and ping 'norom'",
+    ),
+    // settings
+    (
+        "notification_channel.set",
+        "Notifications will be posted in {channel}.",
+    ),
+    (
+        "notification_channel.none",
+        "There is no channel to post notifications in. The server owner will get a dm instead.",
+    ),
+    ("announcements.off", "Changes will not be announced."),
+    ("announcements.on", "Changes will be announced in {channel}."),
+    (
+        "announcements.no_channel",
+        "There is no channel to post announcements in. Set one to see them.",
+    ),
+    ("language.set", "The bot will speak English in this server."),
+    (
+        "language.reset",
+        "The bot will answer everyone in their own language, and use the server's language for notifications.",
+    ),
+    // announcements
+    ("announcement.title", "New {target}"),
+    ("announcement.posted_by", "Posted by **{author}** in {link}"),
+    ("announcement.next_change", "Next change"),
+    // drawn onto the banner
+    ("caption.credit", "art by {authors}"),
+    // problems with a schedule
+    (
+        "notify.lost_banner_feature",
+        "Server has lost the required boost level. Stopping schedule. You can restart the bot after gaining the required boost level.",
+    ),
+    (
+        "notify.lost_invite_splash_feature",
+        "Server has lost the required boost level for an invite splash. Stopping schedule. You can restart the bot after gaining the required boost level.",
+    ),
+    (
+        "notify.not_discoverable",
+        "Server is no longer discoverable, so it can't have a discovery splash. Stopping schedule. You can restart the bot once the server is discoverable again.",
+    ),
+    (
+        "notify.missing_animated_banner_feature",
+        "Tried to set an animated banner but the server does not have the required boost level for animated banners",
+    ),
+    (
+        "notify.missing_animated_icon_feature",
+        "Tried to set an animated icon but the server does not have the required boost level for animated icons",
+    ),
+    (
+        "notify.image_too_big",
+        "An image is too big, even after shrinking it. Discord allows a maximum of 10mb for banners. Consider deleting it.",
+    ),
+    ("notify.image_message", "The image is in this message: {link}"),
+    (
+        "notify.forwarded_from",
+        "It was forwarded from this message: {link}",
+    ),
+    ("notify.image_url", "This is the image: {url}"),
+];
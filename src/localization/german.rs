@@ -0,0 +1,151 @@
+//! German messages
+
+pub const MESSAGES: &[(&str, &str)] = &[
+    // names of schedule targets, used inside sentences
+    ("target.banner", "Banner"),
+    ("target.icon", "Icon"),
+    ("target.invite_splash", "Einladungshintergrund"),
+    ("target.discovery_splash", "Entdeckungshintergrund"),
+    ("target.member_avatar", "Bot-Avatar"),
+    ("target.member_banner", "Bot-Banner"),
+    // errors when running commands
+    ("error.guild_only", "Der Befehl funktioniert nur auf einem Server"),
+    ("error.guild_has_no_banner_set", "Der Server hat kein Banner"),
+    (
+        "error.guild_has_no_banner_feature",
+        "Der Server hat nicht die nötige Boost-Stufe",
+    ),
+    (
+        "error.guild_has_no_animated_banner_feature",
+        "Der Server hat nicht die nötige Boost-Stufe für animierte Banner",
+    ),
+    (
+        "error.guild_has_no_animated_icon_feature",
+        "Der Server hat nicht die nötige Boost-Stufe für animierte Icons",
+    ),
+    (
+        "error.guild_has_no_invite_splash_feature",
+        "Der Server hat nicht die nötige Boost-Stufe für einen Einladungshintergrund",
+    ),
+    (
+        "error.guild_is_not_discoverable",
+        "Nur entdeckbare Server können einen Entdeckungshintergrund haben",
+    ),
+    (
+        "error.splash_cannot_be_animated",
+        "Hintergründe können nicht animiert sein. Wähle ein Standbild oder einen anderen Medienfilter",
+    ),
+    (
+        "error.below_min_interval",
+        "Das Intervall muss mindestens {minutes} Minuten sein",
+    ),
+    (
+        "error.above_max_interval",
+        "Das Intervall darf höchstens {minutes} Minuten sein",
+    ),
+    (
+        "error.message_limit_is_zero",
+        "Das Nachrichtenlimit muss größer als 0 sein",
+    ),
+    (
+        "error.above_max_message_limit",
+        "Das Nachrichtenlimit darf höchstens {limit} sein",
+    ),
+    (
+        "error.lookback_days_is_zero",
+        "Der Rückblick muss mindestens 1 Tag sein",
+    ),
+    (
+        "error.above_max_lookback_days",
+        "Der Rückblick darf höchstens {days} Tage sein",
+    ),
+    (
+        "error.start_time_in_the_past",
+        "Die Startzeit darf nicht in der Vergangenheit liegen. Jetzt={now}, angegeben={given}",
+    ),
+    // /start, /stop and /current_schedule
+    (
+        "start.scheduled",
+        "{target} wechselt alle {interval} Minuten mit Bildern aus {channel}. Start um {start_at} in {seconds} Sekunden.",
+    ),
+    ("stop.stopping", "Dieser Zeitplan wird gestoppt:"),
+    ("schedule.none_running", "Es läuft kein Zeitplan"),
+    ("schedule.channel", "Kanal: "),
+    ("schedule.message_limit", "Nachrichtenlimit: {limit}."),
+    ("schedule.lookback", "Rückblick: {days} Tage."),
+    ("schedule.media", "Medien: {media}."),
+    ("schedule.still_frame", "Standbild: {frame}."),
+    ("schedule.caption", "Beschriftung: {caption}."),
+    ("schedule.collage", "Collage: {collage}."),
+    (
+        "schedule.pool_stats",
+        "Bilder zur Auswahl: {candidates}. Übersprungene Duplikate: {duplicates}.",
+    ),
+    (
+        "schedule.last_reachable_message",
+        "Letzte erreichbare Nachricht: ",
+    ),
+    ("schedule.click_here", "hier klicken"),
+    // /help
+    (
+        "help.join",
+        "Wenn du Hilfe brauchst, komm auf https://discord.gg/MMJFtCtYPP und ping 'norom'",
+    ),
+    // settings
+    (
+        "notification_channel.set",
+        "Benachrichtigungen werden in {channel} gepostet.",
+    ),
+    (
+        "notification_channel.none",
+        "Es gibt keinen Kanal für Benachrichtigungen. Stattdessen bekommt der Serverbesitzer eine Direktnachricht.",
+    ),
+    ("announcements.off", "Wechsel werden nicht angekündigt."),
+    ("announcements.on", "Wechsel werden in {channel} angekündigt."),
+    (
+        "announcements.no_channel",
+        "Es gibt keinen Kanal für Ankündigungen. Lege einen fest, um sie zu sehen.",
+    ),
+    ("language.set", "Der Bot spricht auf diesem Server Deutsch."),
+    (
+        "language.reset",
+        "Der Bot antwortet allen in ihrer eigenen Sprache und benutzt für Benachrichtigungen die Sprache des Servers.",
+    ),
+    // announcements
+    ("announcement.title", "Neu: {target}"),
+    ("announcement.posted_by", "Gepostet von **{author}** in {link}"),
+    ("announcement.next_change", "Nächster Wechsel"),
+    // drawn onto the banner
+    ("caption.credit", "Bild von {authors}"),
+    // problems with a schedule
+    (
+        "notify.lost_banner_feature",
+        "Der Server hat die nötige Boost-Stufe verloren. Der Zeitplan wird gestoppt. Du kannst ihn wieder starten, sobald der Server die Boost-Stufe wieder hat.",
+    ),
+    (
+        "notify.lost_invite_splash_feature",
+        "Der Server hat die nötige Boost-Stufe für einen Einladungshintergrund verloren. Der Zeitplan wird gestoppt. Du kannst ihn wieder starten, sobald der Server die Boost-Stufe wieder hat.",
+    ),
+    (
+        "notify.not_discoverable",
+        "Der Server ist nicht mehr entdeckbar und kann daher keinen Entdeckungshintergrund haben. Der Zeitplan wird gestoppt. Du kannst ihn wieder starten, sobald der Server wieder entdeckbar ist.",
+    ),
+    (
+        "notify.missing_animated_banner_feature",
+        "Ein animiertes Banner konnte nicht gesetzt werden, der Server hat nicht die nötige Boost-Stufe für animierte Banner",
+    ),
+    (
+        "notify.missing_animated_icon_feature",
+        "Ein animiertes Icon konnte nicht gesetzt werden, der Server hat nicht die nötige Boost-Stufe für animierte Icons",
+    ),
+    (
+        "notify.image_too_big",
+        "Ein Bild ist selbst nach dem Verkleinern zu groß. Discord erlaubt höchstens 10mb für Banner. Vielleicht solltest du es löschen.",
+    ),
+    ("notify.image_message", "Das Bild ist in dieser Nachricht: {link}"),
+    (
+        "notify.forwarded_from",
+        "Es wurde von dieser Nachricht weitergeleitet: {link}",
+    ),
+    ("notify.image_url", "Das ist das Bild: {url}"),
+];
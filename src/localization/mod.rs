@@ -0,0 +1,159 @@
+//! Translations of everything the bot says to users
+//!
+//! Every message has an id like `schedule.none_running`. Each language has a catalog of ids to messages.
+//! Messages can have placeholders like `{minutes}`, see [format].
+//! Replies to commands use the guild's language if it has set one with /language, otherwise the user's.
+//! Messages the bot sends on its own use the guild's language, otherwise the server's community language.
+
+mod english;
+mod german;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
+
+use poise::serenity_prelude::GuildId;
+use tracing::warn;
+
+use crate::{Context, Error, State, schedule_target::ScheduleTarget};
+
+/// A language the bot speaks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, poise::ChoiceParameter)]
+pub enum Language {
+    #[default]
+    #[name = "English"]
+    English,
+    #[name = "Deutsch"]
+    German,
+}
+
+impl Language {
+    /// Every language there is
+    pub const ALL: [Self; 2] = [Self::English, Self::German];
+
+    /// The language of a discord locale, like `en-US` or `de`
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let language = locale.split('-').next().unwrap_or_default();
+        match language {
+            "en" => Some(Self::English),
+            "de" => Some(Self::German),
+            _ => None,
+        }
+    }
+
+    /// How the language is stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::German => "de",
+        }
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::English => english::MESSAGES,
+            Self::German => german::MESSAGES,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = LanguageParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Self::English),
+            "de" => Ok(Self::German),
+            other => Err(LanguageParseError(other.to_owned())),
+        }
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown language: {0}")]
+pub struct LanguageParseError(String);
+
+static CATALOGS: LazyLock<HashMap<(Language, &'static str), &'static str>> = LazyLock::new(|| {
+    Language::ALL
+        .into_iter()
+        .flat_map(|language| {
+            language
+                .catalog()
+                .iter()
+                .map(move |(id, message)| ((language, *id), *message))
+        })
+        .collect()
+});
+
+/// The message with this id
+///
+/// Falls back to english if the language doesn't have it, and to the id if no language does
+pub fn tr(language: Language, id: &'static str) -> &'static str {
+    if let Some(message) = CATALOGS.get(&(language, id)) {
+        return message;
+    }
+
+    warn!("Missing message id={id} for language={language}");
+    CATALOGS.get(&(Language::English, id)).copied().unwrap_or(id)
+}
+
+/// The message with this id, with every `{name}` replaced by its value
+pub fn format(language: Language, id: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter()
+        .fold(tr(language, id).to_owned(), |message, (name, value)| {
+            message.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+}
+
+/// Name of a target in a sentence, like "invite splash"
+pub fn target_name(language: Language, target: ScheduleTarget) -> &'static str {
+    let id = match target {
+        ScheduleTarget::Banner => "target.banner",
+        ScheduleTarget::Icon => "target.icon",
+        ScheduleTarget::InviteSplash => "target.invite_splash",
+        ScheduleTarget::DiscoverySplash => "target.discovery_splash",
+        ScheduleTarget::MemberAvatar => "target.member_avatar",
+        ScheduleTarget::MemberBanner => "target.member_banner",
+    };
+    tr(language, id)
+}
+
+/// Language to answer a command in
+///
+/// The guild's language if it has set one, otherwise the language of the user's discord client
+pub async fn command_language(ctx: Context<'_>) -> Result<Language, Error> {
+    let guild_language = match ctx.guild_id() {
+        Some(guild_id) => ctx.data().guild_settings(guild_id).await?.language(),
+        None => None,
+    };
+    if let Some(language) = guild_language {
+        return Ok(language);
+    }
+
+    Ok(ctx.locale().and_then(Language::from_locale).unwrap_or_default())
+}
+
+/// Language for messages the bot sends to a guild on its own
+///
+/// The guild's language if it has set one, otherwise the server's community language
+pub async fn guild_language(
+    ctx: &poise::serenity_prelude::Context,
+    guild_id: GuildId,
+) -> Result<Language, Error> {
+    let state: Arc<State> = ctx.data();
+    if let Some(language) = state.guild_settings(guild_id).await?.language() {
+        return Ok(language);
+    }
+
+    let guild = guild_id.to_partial_guild(&ctx.http).await?;
+    Ok(Language::from_locale(&guild.preferred_locale).unwrap_or_default())
+}
@@ -0,0 +1,155 @@
+//! Classifying media into static and animated images
+
+use std::{fmt::Display, str::FromStr};
+
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Whether an image is animated or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Static,
+    Animated,
+}
+
+impl MediaKind {
+    /// Classify by a content type like `image/gif`
+    ///
+    /// Webp can be both, so it is unknown until downloaded.
+    pub fn from_content_type(content_type: impl AsRef<str>) -> Option<Self> {
+        match content_type.as_ref().to_lowercase().as_str() {
+            "image/png" | "image/jpg" | "image/jpeg" | "image/avif" => Some(Self::Static),
+            "image/gif" => Some(Self::Animated),
+            _ => None,
+        }
+    }
+
+    /// Classify by the file extension in the url path
+    ///
+    /// This is only a guess. Used for embeds, where discord does not tell us the content type.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        let (_, extension) = url.path().rsplit_once('.')?;
+        match extension.to_lowercase().as_str() {
+            "png" | "jpg" | "jpeg" | "avif" => Some(Self::Static),
+            "gif" => Some(Self::Animated),
+            _ => None,
+        }
+    }
+
+    /// Classify by looking at the magic bytes at the start of a file
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        match sniff_format(bytes)? {
+            ImageFormat::Gif => Some(Self::Animated),
+            // the webp header is 12 bytes long, the first chunk follows
+            ImageFormat::WebP => Some(Self::sniff_webp(&bytes[12..])),
+            // animated avifs exist, but they can't be decoded. only the first frame is used
+            _ => Some(Self::Static),
+        }
+    }
+
+    /// `chunk` is everything after the `RIFF....WEBP` header
+    fn sniff_webp(chunk: &[u8]) -> Self {
+        match chunk {
+            // extended format. the second lowest bit of the flags says whether it's animated
+            [b'V', b'P', b'8', b'X', _, _, _, _, flags, ..] if flags & 0x02 != 0 => Self::Animated,
+            _ => Self::Static,
+        }
+    }
+}
+
+/// The format of an image, judged by the magic bytes at the start of the file
+///
+/// Only formats that can be turned into a banner are recognized.
+pub fn sniff_format(bytes: &[u8]) -> Option<ImageFormat> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some(ImageFormat::Png),
+        [0xFF, 0xD8, 0xFF, ..] => Some(ImageFormat::Jpeg),
+        [b'G', b'I', b'F', b'8', ..] => Some(ImageFormat::Gif),
+        [b'R', b'I', b'F', b'F', _, _, _, _, rest @ ..] if rest.starts_with(b"WEBP") => {
+            Some(ImageFormat::WebP)
+        }
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..]
+            if cfg!(feature = "avif") && matches!(brand.get(..4), Some(b"avif" | b"avis")) =>
+        {
+            Some(ImageFormat::Avif)
+        }
+        _ => None,
+    }
+}
+
+/// The format of a downloaded image
+///
+/// Files are often mislabeled, so the magic bytes win.
+/// The content type header is only used when the bytes are not recognized.
+pub fn detect_format(bytes: &[u8], content_type: Option<&str>) -> Option<ImageFormat> {
+    sniff_format(bytes).or_else(|| {
+        let content_type = content_type?.split(';').next()?.trim().to_lowercase();
+        match ImageFormat::from_mime_type(content_type)? {
+            format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP) => {
+                Some(format)
+            }
+            ImageFormat::Avif if cfg!(feature = "avif") => Some(ImageFormat::Avif),
+            _ => None,
+        }
+    })
+}
+
+/// Which kinds of media a schedule is allowed to pick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum MediaFilter {
+    #[default]
+    #[name = "Any"]
+    Any,
+    #[name = "Static only"]
+    StaticOnly,
+    #[name = "Animated only"]
+    AnimatedOnly,
+}
+
+impl MediaFilter {
+    /// Does the filter let media of this kind through?
+    ///
+    /// Media of unknown kind is only let through if static media is allowed.
+    /// Downloaded images are checked again, once their bytes tell the truth.
+    pub fn allows(self, kind: Option<MediaKind>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::StaticOnly => kind != Some(MediaKind::Animated),
+            Self::AnimatedOnly => kind == Some(MediaKind::Animated),
+        }
+    }
+
+    /// How the filter is stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::StaticOnly => "static",
+            Self::AnimatedOnly => "animated",
+        }
+    }
+}
+
+impl FromStr for MediaFilter {
+    type Err = MediaFilterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(Self::Any),
+            "static" => Ok(Self::StaticOnly),
+            "animated" => Ok(Self::AnimatedOnly),
+            other => Err(MediaFilterParseError(other.to_owned())),
+        }
+    }
+}
+
+impl Display for MediaFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown media filter: {0}")]
+pub struct MediaFilterParseError(String);
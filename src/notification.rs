@@ -0,0 +1,91 @@
+//! Telling a guild about problems with its schedules
+//!
+//! Messages go to the guild's notification channel, see [GuildSettings](crate::database::guild_settings::GuildSettings).
+//! Posters of images that can't be used get a reaction on their message.
+
+use std::sync::Arc;
+
+use poise::serenity_prelude::{Context, CreateMessage, GenericChannelId, GuildId, Message, ReactionType};
+use tracing::{debug, warn};
+
+use crate::{
+    Error, Settings, State,
+    utils::{dm_user, truncate_to_discord_limit},
+};
+
+/// Where messages about the guild go
+///
+/// The channel set with /notification_channel. If there is none, the guild's system channel.
+/// If there is none either, the channel /start was last run in.
+pub async fn notification_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+) -> Result<Option<GenericChannelId>, Error> {
+    let state: Arc<State> = ctx.data();
+    let settings = state.guild_settings(guild_id).await?;
+
+    if let Some(channel_id) = settings.notification_channel() {
+        return Ok(Some(channel_id));
+    }
+
+    let guild = guild_id.to_partial_guild(&ctx.http).await?;
+    if let Some(channel_id) = guild.system_channel_id {
+        return Ok(Some(channel_id.widen()));
+    }
+
+    Ok(settings.start_channel())
+}
+
+/// Post `content` in the guild's notification channel
+///
+/// If there is no channel or the bot can't post there, the guild owner gets a dm instead
+pub async fn notify_guild(ctx: &Context, guild_id: GuildId, content: &str) -> Result<(), Error> {
+    let content = truncate_to_discord_limit(content);
+
+    if let Some(channel_id) = notification_channel(ctx, guild_id).await? {
+        debug!("Notifying guild={guild_id} in channel={channel_id}: {content}");
+        match channel_id
+            .send_message(&ctx.http, CreateMessage::new().content(content))
+            .await
+        {
+            Ok(_) => return Ok(()),
+            Err(err) => warn!("Could not notify guild={guild_id} in channel={channel_id}: {err}"),
+        }
+    }
+
+    let guild_owner = guild_id.to_partial_guild(&ctx.http).await?.owner_id;
+    debug!("Notifying owner={guild_owner} of guild={guild_id} instead");
+    dm_user(ctx, guild_owner, content).await?;
+
+    Ok(())
+}
+
+/// React to the message of an image that can't be used, so the poster knows why it never shows up
+///
+/// Reacts only once per message, no matter how often the image is picked.
+/// If someone removes the reaction, it comes back the next time the image is picked
+pub async fn react_to_rejected_image(ctx: &Context, message: &Message) -> Result<(), Error> {
+    let emoji = &Settings::get().reactions.rejected_image;
+    if emoji.is_empty() {
+        return Ok(());
+    }
+
+    let Ok(reaction) = emoji.parse::<ReactionType>() else {
+        warn!("Not reacting to rejected image, {emoji} is not an emoji");
+        return Ok(());
+    };
+
+    if message
+        .reactions
+        .iter()
+        .any(|existing| existing.me && existing.reaction_type == reaction)
+    {
+        debug!("Already reacted to rejected image in message={}", message.id);
+        return Ok(());
+    }
+
+    debug!("Reacting to rejected image in message={}", message.id);
+    message.react(&ctx.http, reaction).await?;
+
+    Ok(())
+}
@@ -0,0 +1,353 @@
+//! Telling the bot owners about problems in guilds
+//!
+//! With many guilds, a dm for every failing schedule floods the owners' dms.
+//! Only errors of at least the `immediate` severity are sent right away.
+//! Everything down to the `minimum` severity is collected and sent as a digest every `digest_interval` minutes,
+//! grouped by guild and kind of error. See [OwnerNotifications](crate::settings::OwnerNotifications).
+//!
+//! Errors sent right away are still rate limited: the same kind of error in the same guild
+//! is sent once per `alert_cooldown` minutes. Repeats within that window are counted,
+//! and reported once the window closes.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, hash_map::Entry},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use poise::serenity_prelude::{Context, GuildId, MessageBuilder, UserId};
+use serde::Deserialize;
+use tokio::time::interval;
+use tracing::{debug, warn};
+
+use crate::{
+    Error, Settings, State,
+    constants::DISCORD_MESSAGE_CONTENT_LIMIT,
+    schedule_runner::RunnerError,
+    utils::{current_unix_timestamp, dm_users},
+};
+
+/// Longest error message shown in a digest, in characters
+const DIGEST_MESSAGE_LENGTH: usize = 300;
+
+/// Seconds between looking for closed alert windows
+const ALERT_WINDOW_CHECK_INTERVAL: u64 = 60;
+
+/// How urgently the bot owners need to know about an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Problems with single images. The guild was told about them if it needs to act
+    Info,
+    /// The guild lost a feature, or discord or an image host had a hiccup
+    Warning,
+    /// Something in the bot went wrong
+    Error,
+    /// The scheduler itself is broken
+    Critical,
+}
+
+/// Repeats of the same kind of error in the same guild
+#[derive(Debug)]
+struct DigestEntry {
+    guild_name: String,
+    count: u64,
+    last_seen: u64,
+    last_message: String,
+}
+
+/// Owner notifications waiting for the next digest
+#[derive(Debug, Default)]
+pub struct OwnerDigest {
+    entries: Mutex<BTreeMap<(GuildId, &'static str), DigestEntry>>,
+}
+
+impl OwnerDigest {
+    /// Add an error. Repeats are counted, only the latest message is kept
+    pub fn add(&self, guild_id: GuildId, guild_name: &str, category: &'static str, message: &str) {
+        let mut entries = self.entries.lock().expect("digest lock is never poisoned");
+        let entry = entries
+            .entry((guild_id, category))
+            .or_insert_with(|| DigestEntry {
+                guild_name: guild_name.to_owned(),
+                count: 0,
+                last_seen: 0,
+                last_message: String::new(),
+            });
+
+        entry.count += 1;
+        entry.last_seen = current_unix_timestamp();
+        entry.last_message = shorten(message);
+    }
+
+    /// Take everything collected so far, as messages that fit into discord's limit.
+    /// Empty if nothing happened
+    pub fn take_messages(&self) -> Vec<String> {
+        let entries = std::mem::take(&mut *self.entries.lock().expect("digest lock is never poisoned"));
+
+        let lines = entries.into_iter().map(|((guild_id, category), entry)| {
+            let line = format!(
+                "- `{category}`: {} times, last <t:{}:R>: {}\n",
+                entry.count, entry.last_seen, entry.last_message
+            );
+            (guild_id, entry.guild_name, line)
+        });
+
+        group_by_guild("**Errors since the last digest**\n", lines)
+    }
+}
+
+/// An alert that was sent, and the repeats that were not
+#[derive(Debug)]
+struct AlertWindow {
+    guild_name: String,
+    opened_at: u64,
+    suppressed: u64,
+    last_message: String,
+}
+
+/// Repeats of an alert that were not sent
+#[derive(Debug)]
+struct SuppressedAlerts {
+    guild_id: GuildId,
+    guild_name: String,
+    category: &'static str,
+    count: u64,
+    since: u64,
+    last_message: String,
+}
+
+#[derive(Debug, Default)]
+struct AlertWindows {
+    open: HashMap<(GuildId, &'static str), AlertWindow>,
+    /// Closed windows with suppressed alerts, waiting to be reported
+    closed: Vec<SuppressedAlerts>,
+}
+
+impl AlertWindows {
+    /// Close every window that is older than the cooldown
+    fn close_expired(&mut self, now: u64, cooldown: u64) {
+        let closed = &mut self.closed;
+        self.open.retain(|(guild_id, category), window| {
+            if now < window.opened_at + cooldown {
+                return true;
+            }
+
+            if window.suppressed > 0 {
+                closed.push(SuppressedAlerts {
+                    guild_id: *guild_id,
+                    guild_name: std::mem::take(&mut window.guild_name),
+                    category: *category,
+                    count: window.suppressed,
+                    since: window.opened_at,
+                    last_message: std::mem::take(&mut window.last_message),
+                });
+            }
+            false
+        });
+    }
+}
+
+/// Sends the same kind of alert for the same guild only once per cooldown
+#[derive(Debug, Default)]
+pub struct AlertLimiter {
+    windows: Mutex<AlertWindows>,
+}
+
+impl AlertLimiter {
+    /// Should this alert be sent? If not, it is counted for the report when the window closes
+    pub fn allow(&self, guild_id: GuildId, guild_name: &str, category: &'static str, message: &str) -> bool {
+        let now = current_unix_timestamp();
+        let mut windows = self.windows.lock().expect("alert lock is never poisoned");
+        windows.close_expired(now, alert_cooldown());
+
+        match windows.open.entry((guild_id, category)) {
+            Entry::Occupied(mut entry) => {
+                let window = entry.get_mut();
+                window.suppressed += 1;
+                window.last_message = shorten(message);
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(AlertWindow {
+                    guild_name: guild_name.to_owned(),
+                    opened_at: now,
+                    suppressed: 0,
+                    last_message: String::new(),
+                });
+                true
+            }
+        }
+    }
+
+    /// Reports of the alerts suppressed in windows that have closed, as messages that fit into discord's limit.
+    /// Empty if nothing was suppressed
+    pub fn take_reports(&self) -> Vec<String> {
+        let mut closed = {
+            let mut windows = self.windows.lock().expect("alert lock is never poisoned");
+            windows.close_expired(current_unix_timestamp(), alert_cooldown());
+            std::mem::take(&mut windows.closed)
+        };
+        closed.sort_by_key(|suppressed| suppressed.guild_id);
+
+        let lines = closed.into_iter().map(|suppressed| {
+            let line = format!(
+                "- `{}`: {} more times since <t:{}:R>, last: {}\n",
+                suppressed.category, suppressed.count, suppressed.since, suppressed.last_message
+            );
+            (suppressed.guild_id, suppressed.guild_name, line)
+        });
+
+        group_by_guild("**Repeated errors that were not sent**\n", lines)
+    }
+}
+
+/// Cooldown of alerts in seconds
+fn alert_cooldown() -> u64 {
+    Settings::get().owner_notifications.alert_cooldown * 60
+}
+
+/// Put lines under a header for their guild, split into messages that fit into discord's limit.
+/// Lines of a guild must come one after another. Empty if there are no lines
+fn group_by_guild(title: &str, lines: impl IntoIterator<Item = (GuildId, String, String)>) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::from(title);
+    let mut last_guild = None;
+
+    for (guild_id, guild_name, line) in lines {
+        let header = MessageBuilder::new().push_bold_line_safe(&*guild_name).build();
+
+        if current.len() + header.len() + line.len() > DISCORD_MESSAGE_CONTENT_LIMIT {
+            messages.push(std::mem::take(&mut current));
+            last_guild = None;
+        }
+
+        // repeat the guild at the start of every message
+        if last_guild != Some(guild_id) {
+            current.push_str(&header);
+            last_guild = Some(guild_id);
+        }
+        current.push_str(&line);
+    }
+
+    if last_guild.is_some() {
+        messages.push(current);
+    }
+    messages
+}
+
+/// Tell the bot owners about an error in a schedule: right away, in the next digest or not at all
+pub async fn notify_owners(
+    ctx: &Context,
+    owners: HashSet<UserId>,
+    error: &RunnerError,
+    guild_name: &str,
+) -> Result<(), Error> {
+    let settings = &Settings::get().owner_notifications;
+    let severity = error.source().severity();
+
+    if severity < settings.minimum {
+        debug!(
+            "Not telling owners about {severity:?} error in guild={}",
+            error.guild_id()
+        );
+        return Ok(());
+    }
+
+    if severity >= settings.immediate {
+        let message = MessageBuilder::new()
+            .push_bold("Error in guild: ")
+            .push_line_safe(guild_name)
+            .push_line("")
+            .push(&*error.to_string())
+            .build();
+
+        return alert_owners(
+            ctx,
+            owners,
+            error.guild_id(),
+            guild_name,
+            error.source().category(),
+            &message,
+        )
+        .await;
+    }
+
+    let state: Arc<State> = ctx.data();
+    state.owner_digest().add(
+        error.guild_id(),
+        guild_name,
+        error.source().category(),
+        &error.to_string(),
+    );
+
+    Ok(())
+}
+
+/// Dm the bot owners about an error in a guild, unless the same kind of error was sent within the cooldown
+pub async fn alert_owners(
+    ctx: &Context,
+    owners: HashSet<UserId>,
+    guild_id: GuildId,
+    guild_name: &str,
+    category: &'static str,
+    message: &str,
+) -> Result<(), Error> {
+    let state: Arc<State> = ctx.data();
+    if !state
+        .alert_limiter()
+        .allow(guild_id, guild_name, category, message)
+    {
+        debug!("Suppressing repeated {category} alert for guild={guild_id}");
+        return Ok(());
+    }
+
+    dm_users(ctx, owners, message).await
+}
+
+/// Tell the bot owners how often alerts were suppressed, once their cooldown is over
+pub async fn report_suppressed_alerts(ctx: Context) {
+    let mut ticker = interval(Duration::from_secs(ALERT_WINDOW_CHECK_INTERVAL));
+
+    loop {
+        ticker.tick().await;
+
+        let state: Arc<State> = ctx.data();
+        for message in state.alert_limiter().take_reports() {
+            if let Err(err) = dm_users(&ctx, state.owners(), &message).await {
+                warn!("Could not report suppressed alerts to owners: {err}");
+            }
+        }
+    }
+}
+
+/// Send the collected errors to the bot owners every `digest_interval` minutes
+///
+/// Errors collected since the last digest are lost when the bot shuts down
+pub async fn send_digests(ctx: Context) {
+    let minutes = Settings::get().owner_notifications.digest_interval.max(1);
+    let mut ticker = interval(Duration::from_secs(minutes * 60));
+
+    // the first tick completes right away, there is nothing to send yet
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let state: Arc<State> = ctx.data();
+        for message in state.owner_digest().take_messages() {
+            if let Err(err) = dm_users(&ctx, state.owners(), &message).await {
+                warn!("Could not send digest to owners: {err}");
+            }
+        }
+    }
+}
+
+/// Single line of at most [DIGEST_MESSAGE_LENGTH] characters
+fn shorten(message: &str) -> String {
+    let message = message.replace('\n', " ");
+    match message.char_indices().nth(DIGEST_MESSAGE_LENGTH) {
+        Some((end, _)) => format!("{}…", &message[..end]),
+        None => message,
+    }
+}
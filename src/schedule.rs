@@ -8,18 +8,28 @@ use async_repeater::{Delay, RepeaterEntry};
 use poise::serenity_prelude::{GenericChannelId, GuildId};
 
 use crate::{
+    caption::CaptionPosition,
+    collage::CollageLayout,
     database::guild_schedule::GuildSchedule,
+    media_kind::MediaFilter,
+    schedule_target::{ScheduleKey, ScheduleTarget},
     utils::{current_unix_timestamp, next_run},
 };
 
 #[derive(Clone)]
 pub struct Schedule {
     guild_id: GuildId,
+    target: ScheduleTarget,
     channel_id: GenericChannelId,
     interval: NonZeroU64,
     start_at: u64,
     last_run: Option<NonZeroU64>,
     message_limit: Option<NonZeroU32>,
+    lookback_days: Option<NonZeroU32>,
+    media_filter: MediaFilter,
+    still_frame: Option<NonZeroU32>,
+    caption: Option<CaptionPosition>,
+    collage: Option<CollageLayout>,
 }
 
 impl Schedule {
@@ -28,6 +38,16 @@ impl Schedule {
         self.guild_id
     }
 
+    /// What the schedule changes
+    pub fn target(&self) -> ScheduleTarget {
+        self.target
+    }
+
+    /// Identifies the schedule among all schedules of all guilds
+    pub fn key(&self) -> ScheduleKey {
+        ScheduleKey::new(self.guild_id, self.target)
+    }
+
     /// Which channel to source images from
     pub fn channel_id(&self) -> GenericChannelId {
         self.channel_id
@@ -53,6 +73,38 @@ impl Schedule {
         self.message_limit
     }
 
+    /// How many days to look into the past for
+    pub fn lookback_days(&self) -> Option<NonZeroU32> {
+        self.lookback_days
+    }
+
+    /// Unix timestamp of the oldest message to look at, if looking back by days
+    pub fn lookback_cutoff(&self) -> Option<u64> {
+        self.lookback_days
+            .map(|days| current_unix_timestamp().saturating_sub(u64::from(days.get()) * 60 * 60 * 24))
+    }
+
+    /// Which kinds of media may be picked
+    pub fn media_filter(&self) -> MediaFilter {
+        self.media_filter
+    }
+
+    /// Which frame of animated images to use as a static banner, on guilds without animated banners.
+    /// 1 is the first frame. `None` means animated images are not used on those guilds
+    pub fn still_frame(&self) -> Option<NonZeroU32> {
+        self.still_frame
+    }
+
+    /// Where to credit the artist on the banner. `None` means no caption
+    pub fn caption(&self) -> Option<CaptionPosition> {
+        self.caption
+    }
+
+    /// How to tile several images into one banner. `None` means a single image is used
+    pub fn collage(&self) -> Option<CollageLayout> {
+        self.collage
+    }
+
     /// How many seconds the `last_run` is late
     pub fn lag(&self) -> Option<u64> {
         self.last_run.map(|x| x.get() % self.interval)
@@ -61,25 +113,43 @@ impl Schedule {
 
 pub struct ScheduleBuilder {
     guild_id: GuildId,
+    target: ScheduleTarget,
     channel_id: GenericChannelId,
     interval: NonZeroU64,
     start_at: u64,
     last_run: Option<NonZeroU64>,
     message_limit: Option<NonZeroU32>,
+    lookback_days: Option<NonZeroU32>,
+    media_filter: MediaFilter,
+    still_frame: Option<NonZeroU32>,
+    caption: Option<CaptionPosition>,
+    collage: Option<CollageLayout>,
 }
 
 impl ScheduleBuilder {
     pub fn new(guild_id: GuildId, channel_id: GenericChannelId, interval: NonZeroU64) -> Self {
         Self {
             guild_id,
+            target: ScheduleTarget::default(),
             channel_id,
             interval,
             start_at: current_unix_timestamp(),
             last_run: None,
             message_limit: None,
+            lookback_days: None,
+            media_filter: MediaFilter::default(),
+            still_frame: None,
+            caption: None,
+            collage: None,
         }
     }
 
+    #[must_use]
+    pub fn target(mut self, target: ScheduleTarget) -> Self {
+        self.target = target;
+        self
+    }
+
     #[must_use]
     pub fn start_at(mut self, start_at: u64) -> Self {
         self.start_at = start_at;
@@ -92,35 +162,77 @@ impl ScheduleBuilder {
         self
     }
 
+    #[must_use]
+    pub fn lookback_days(mut self, lookback_days: u32) -> Self {
+        self.lookback_days = NonZeroU32::new(lookback_days);
+        self
+    }
+
+    #[must_use]
+    pub fn media_filter(mut self, media_filter: MediaFilter) -> Self {
+        self.media_filter = media_filter;
+        self
+    }
+
+    #[must_use]
+    pub fn still_frame(mut self, still_frame: u32) -> Self {
+        self.still_frame = NonZeroU32::new(still_frame);
+        self
+    }
+
+    #[must_use]
+    pub fn caption(mut self, caption: Option<CaptionPosition>) -> Self {
+        self.caption = caption;
+        self
+    }
+
+    #[must_use]
+    pub fn collage(mut self, collage: Option<CollageLayout>) -> Self {
+        self.collage = collage;
+        self
+    }
+
     pub fn build(self) -> Schedule {
         let ScheduleBuilder {
             guild_id,
+            target,
             channel_id,
             interval,
             start_at,
             last_run,
             message_limit,
+            lookback_days,
+            media_filter,
+            still_frame,
+            caption,
+            collage,
         } = self;
         Schedule {
             guild_id,
+            target,
             channel_id,
             interval,
             start_at,
             last_run,
             message_limit,
+            lookback_days,
+            media_filter,
+            still_frame,
+            caption,
+            collage,
         }
     }
 }
 
 impl RepeaterEntry for Schedule {
-    type Key = GuildId;
+    type Key = ScheduleKey;
 
     fn interval(&self) -> Duration {
         Duration::from_secs(self.interval.get())
     }
 
     fn key(&self) -> Self::Key {
-        self.guild_id
+        self.key()
     }
 
     fn delay(&self) -> Delay {
@@ -134,19 +246,29 @@ impl Debug for Schedule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self {
             guild_id,
+            target,
             channel_id,
             interval,
             start_at,
             last_run,
             message_limit,
+            lookback_days,
+            media_filter,
+            still_frame,
+            caption,
+            collage,
         } = self;
 
         let last_run = last_run.map(NonZeroU64::get).unwrap_or_default();
         let message_limit = message_limit.map(NonZeroU32::get).unwrap_or_default();
+        let lookback_days = lookback_days.map(NonZeroU32::get).unwrap_or_default();
+        let still_frame = still_frame.map(NonZeroU32::get).unwrap_or_default();
+        let caption = caption.map_or("none", CaptionPosition::as_str);
+        let collage = collage.map_or("none", CollageLayout::as_str);
 
         write!(
             f,
-            "Schedule(guild={guild_id}, channel={channel_id}, interval={interval}, start_at={start_at}, last_run={last_run}, message_limit={message_limit}",
+            "Schedule(guild={guild_id}, target={target}, channel={channel_id}, interval={interval}, start_at={start_at}, last_run={last_run}, message_limit={message_limit}, lookback_days={lookback_days}, media_filter={media_filter}, still_frame={still_frame}, caption={caption}, collage={collage}",
         )
     }
 }
@@ -154,19 +276,31 @@ impl Debug for Schedule {
 impl From<GuildSchedule> for Schedule {
     fn from(guild_schedule: GuildSchedule) -> Self {
         let guild_id = guild_schedule.guild_id();
+        let target = guild_schedule.target();
         let channel = guild_schedule.channel_id();
         let interval = guild_schedule.interval();
         let start_at = guild_schedule.start_at();
         let last_run = guild_schedule.last_run();
         let message_limit = guild_schedule.message_limit();
+        let lookback_days = guild_schedule.lookback_days();
+        let media_filter = guild_schedule.media_filter();
+        let still_frame = guild_schedule.still_frame();
+        let caption = guild_schedule.caption();
+        let collage = guild_schedule.collage();
 
         Schedule {
             guild_id: GuildId::new(guild_id),
+            target,
             channel_id: GenericChannelId::new(channel),
             interval,
             start_at,
             last_run: NonZeroU64::new(last_run),
             message_limit: NonZeroU32::new(message_limit.try_into().unwrap_or(u32::MAX)),
+            lookback_days: NonZeroU32::new(lookback_days.try_into().unwrap_or(u32::MAX)),
+            media_filter,
+            still_frame: NonZeroU32::new(still_frame.try_into().unwrap_or(u32::MAX)),
+            caption,
+            collage,
         }
     }
 }
@@ -1,22 +1,28 @@
 use std::{num::NonZeroU32, sync::Arc, time::Duration};
 
 use poise::serenity_prelude::{self, GuildId, Message};
-use tokio::{
-    pin,
-    time::{sleep, timeout},
-};
-use tokio_stream::StreamExt;
-use tracing::{debug, error, info, instrument};
+use rand::seq::IteratorRandom;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, error, info, instrument, warn};
 use url::Url;
 
 use crate::{
     Error, State,
-    database::{Database, guild_schedule::GuildSchedule},
+    announcement::announce_change,
+    collage::CollageLayout,
+    crop_hint::CropHint,
+    database::{Database, guild_schedule::GuildSchedule, media_index::IndexedMedia},
+    deduplication::{PoolStats, collapse_duplicates, media_identity},
     error::evaluate_schedule_error,
-    finding_media::find_media_in_channel,
+    finding_media::media_source_channels,
+    indexing::{ensure_indexed, media_in_reach, resolve_indexed_media},
+    localization::{Language, guild_language},
+    media_kind::MediaKind,
+    notification::react_to_rejected_image,
+    owner_digest::alert_owners,
     schedule::Schedule,
-    setting_banner::{BannerFromUrl, RandomBanner, SetBannerError},
-    utils::dm_users,
+    setting_banner::{BannerFromUrl, SetBannerError, check_target_features},
+    webhook::{self, Event, EventKind},
 };
 
 pub struct ScheduleRunner {
@@ -51,69 +57,241 @@ impl ScheduleRunner {
         let schedule = self.schedule.clone();
         let mut guild_id = schedule.guild_id();
 
+        // a single request covers every feature the target needs. done before downloading anything
+        let animated_allowed = check_target_features(&self.ctx.http, guild_id, schedule.target())
+            .await
+            .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
+
+        // only the caption is in the guild's language, so don't look it up without one
+        let language = match schedule.caption() {
+            Some(_) => guild_language(&self.ctx, guild_id)
+                .await
+                .map_err(|err| RunnerError::new(err, guild_id, self.schedule.clone()))?,
+            None => Language::default(),
+        };
+
         // if we have an override image given, just use it and skip the rest of the function
         if let Some((url, message)) = pick_this {
             debug!("Using override image: {url}");
             guild_id
-                .set_banner_from_url_and_message(self.ctx.http.clone(), &self.http_client, &url, &message)
+                .set_banner_from_url_and_message(
+                    self.ctx.http.clone(),
+                    &self.http_client,
+                    &url,
+                    &message,
+                    &schedule,
+                    animated_allowed,
+                    CropHint::from_content(&message.content),
+                    language,
+                )
                 .await
                 .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
             debug!("Inserting schedule into database");
             let schedule = GuildSchedule::from(schedule.clone());
 
             self.database
-                .insert(&schedule, schedule.guild_id())
+                .insert(&schedule, schedule.key().to_string())
                 .await
                 .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
+
+            self.announce(&[(url.clone(), message)]).await;
             return Ok(url);
         };
 
         let channel = schedule.channel_id();
         let limit = schedule.message_limit().map_or(u32::MAX, NonZeroU32::get);
+        let not_before = schedule.lookback_cutoff();
 
-        debug!("Fetching images, limited to {} messages", limit);
+        let media_filter = schedule.media_filter();
+        // animated images can still be used as a still frame. collages always use one
+        let still_frame = schedule.still_frame().is_some() || schedule.collage().is_some();
 
-        let stream_of_media = find_media_in_channel(&self.ctx, &channel, limit as usize);
-        pin!(stream_of_media);
+        let sources = media_source_channels(&self.ctx, channel, limit as usize)
+            .await
+            .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
 
-        let mut images = Vec::new();
-        while let Some(url_message_pair) = stream_of_media
-            .try_next()
+        let mut pool = Vec::new();
+        for (source, limit) in sources {
+            debug!("Looking up indexed images in {source}, limited to {limit} messages");
+
+            ensure_indexed(
+                &self.ctx,
+                &self.database,
+                &self.http_client,
+                guild_id,
+                source,
+                limit,
+                not_before,
+            )
             .await
-            .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?
-            .map(|media| {
-                (
-                    Url::parse(&media.media).expect("every media should have a valid url"),
-                    media.message,
-                )
-            })
-        {
-            if avoid_list.contains(&url_message_pair.0) {
-                continue;
-            }
+            .map_err(|err| RunnerError::new(err, guild_id, self.schedule.clone()))?;
 
-            images.push(url_message_pair);
+            let media = media_in_reach(&self.database, source, limit, not_before)
+                .await
+                .map_err(|err| RunnerError::new(err, guild_id, self.schedule.clone()))?;
+
+            pool.extend(media.into_iter().filter(|entry| {
+                // filter beforehand, so guilds without animated images never pick a gif in the first place
+                let is_animated = entry.kind == Some(MediaKind::Animated);
+                media_filter.allows(entry.kind)
+                    && !(is_animated && !animated_allowed && !still_frame)
+                    && !is_avoided(avoid_list, &entry.url)
+            }));
         }
 
-        let img_count = images.len();
-        debug!("Fetched {} images. Setting banner", img_count);
-        let new_banner = guild_id
-            .set_random_banner_with_message(self.ctx.http.clone(), &self.http_client, &images)
+        // only ask about the candidates, the marks of every image ever seen would be a lot
+        let identities: Vec<String> = pool.iter().map(|entry| media_identity(&entry.url)).collect();
+        let corrupt_images = self
+            .database
+            .corrupt_images(&identities)
             .await
             .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
+        pool.retain(|entry| !corrupt_images.contains(&media_identity(&entry.url)));
+
+        let (mut pool, duplicates) = collapse_duplicates(&self.database, pool)
+            .await
+            .map_err(|err| RunnerError::new(err, guild_id, self.schedule.clone()))?;
+
+        debug!(
+            "Found {} indexed images, skipped {duplicates} duplicates. Setting {}",
+            pool.len(),
+            schedule.target()
+        );
+        let stats = PoolStats {
+            candidates: pool.len(),
+            duplicates,
+        };
+        self.database
+            .set_pool_stats(schedule.key(), stats)
+            .await
+            .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
+
+        let wanted = schedule.collage().map_or(1, CollageLayout::image_count);
+        let mut picked = Vec::with_capacity(wanted);
+        let mut crop_hints = Vec::with_capacity(wanted);
+        while picked.len() < wanted {
+            let Some((media, crop_hint)) = self.pick_media(&mut pool, avoid_list).await? else {
+                break;
+            };
+            picked.push(media);
+            crop_hints.push(crop_hint);
+        }
+
+        let used = match (schedule.collage(), picked.as_slice()) {
+            (_, []) => {
+                return Err(RunnerError::new(
+                    SetBannerError::CouldNotPickAUrl.into(),
+                    guild_id,
+                    self.schedule.clone(),
+                ));
+            }
+            (Some(layout), media) if media.len() == wanted => {
+                guild_id
+                    .set_collage_from_urls_and_messages(
+                        self.ctx.http.clone(),
+                        &self.http_client,
+                        media,
+                        layout,
+                        &schedule,
+                        &crop_hints,
+                        language,
+                    )
+                    .await
+                    .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
+                media
+            }
+            // also used when there are not enough images to fill the collage
+            (_, [first @ (url, message), ..]) => {
+                guild_id
+                    .set_banner_from_url_and_message(
+                        self.ctx.http.clone(),
+                        &self.http_client,
+                        url,
+                        message,
+                        &schedule,
+                        animated_allowed,
+                        crop_hints[0],
+                        language,
+                    )
+                    .await
+                    .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
+                std::slice::from_ref(first)
+            }
+        };
 
         debug!("Inserting schedule into database");
         let schedule = GuildSchedule::from(schedule);
 
         self.database
-            .insert(&schedule, schedule.guild_id())
+            .insert(&schedule, schedule.key().to_string())
             .await
             .map_err(|err| RunnerError::new(err.into(), guild_id, self.schedule.clone()))?;
 
-        Ok(new_banner.to_owned())
+        self.announce(used).await;
+
+        Ok(used[0].0.clone())
+    }
+
+    /// Post the change in the guild, if it wants that. The change already happened, so failing is fine
+    async fn announce(&self, media: &[(Url, Message)]) {
+        if let Err(err) = announce_change(&self.ctx, &self.schedule, media).await {
+            warn!(
+                "Could not announce change in guild={}: {err}",
+                self.schedule.guild_id()
+            );
+        }
+    }
+
+    /// Take a random image out of the pool.
+    ///
+    /// Attachment urls in the index might have expired, so the message is fetched again.
+    /// If the message is gone, the next one is tried. `None` once the pool is empty
+    ///
+    /// Comes with the crop hint from the index, unless the text of the fetched message has one.
+    async fn pick_media(
+        &self,
+        pool: &mut Vec<IndexedMedia>,
+        avoid_list: &[Url],
+    ) -> Result<Option<((Url, Message), Option<CropHint>)>, RunnerError> {
+        let guild_id = self.schedule.guild_id();
+
+        loop {
+            let Some(picked) = (0..pool.len()).choose(&mut rand::rng()) else {
+                return Ok(None);
+            };
+            let entry = pool.swap_remove(picked);
+
+            let Some(media) = resolve_indexed_media(&self.ctx, &self.database, &self.http_client, &entry)
+                .await
+                .map_err(|err| RunnerError::new(err, guild_id, self.schedule.clone()))?
+            else {
+                continue;
+            };
+
+            let url = Url::parse(&media.media).expect("every media should have a valid url");
+            if is_avoided(avoid_list, url.as_str()) {
+                continue;
+            }
+
+            let crop_hint = CropHint::from_content(&media.message.content).or(entry.crop_hint);
+            return Ok(Some(((url, media.message), crop_hint)));
+        }
     }
 }
 
+/// Was this image tried before?
+///
+/// Only compares the path. Discord signs attachment urls, so the query changes each time the message is fetched
+fn is_avoided(avoid_list: &[Url], url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+
+    avoid_list
+        .iter()
+        .any(|avoided| avoided.host() == url.host() && avoided.path() == url.path())
+}
+
 #[derive(Debug)]
 pub enum ScheduleAction {
     /// Everything's fine
@@ -158,15 +336,26 @@ impl RunnerError {
                 SetBannerError::Transport(_) => (None, None),
                 SetBannerError::DiscordApi(_) => (None, None),
                 SetBannerError::CouldNotPickAUrl => (None, None),
-                SetBannerError::CouldNotDeterminFileExtension(url) => (Some(url.clone()), None),
+                SetBannerError::UnknownImageFormat(url, message) => {
+                    (Some(url.clone()), Some(message.clone()))
+                }
                 SetBannerError::MissingBannerFeature => (None, None),
                 SetBannerError::MissingAnimatedBannerFeature(url, message) => {
                     (Some(url.clone()), Some(message.clone()))
                 }
+                SetBannerError::MissingAnimatedIconFeature(url, message) => {
+                    (Some(url.clone()), Some(message.clone()))
+                }
+                SetBannerError::MissingInviteSplashFeature => (None, None),
+                SetBannerError::MissingDiscoverableFeature => (None, None),
+                SetBannerError::AnimatedSplash(url, message) => (Some(url.clone()), Some(message.clone())),
+                SetBannerError::FilteredMediaKind(url, message) => (Some(url.clone()), Some(message.clone())),
                 SetBannerError::ImageIsEmpty(url, message) => (Some(url.clone()), Some(message.clone())),
                 SetBannerError::ImageIsTooBig(url, message) => (Some(url.clone()), Some(message.clone())),
                 SetBannerError::ImageUnkownSize(url, message) => (Some(url.clone()), Some(message.clone())),
                 SetBannerError::Base64Encoding(url, message) => (Some(url.clone()), Some(message.clone())),
+                SetBannerError::ImageProcessing(url, message) => (Some(url.clone()), Some(message.clone())),
+                SetBannerError::ImageIsCorrupt(url, message) => (Some(url.clone()), Some(message.clone())),
             },
             _ => (None, None),
         }
@@ -175,6 +364,14 @@ impl RunnerError {
     pub fn source(&self) -> &crate::Error {
         &self.source
     }
+
+    /// The message of the image, if the image itself can't be used. See [SetBannerError::rejected_message]
+    pub fn rejected_message(&self) -> Option<&Message> {
+        match &self.source {
+            Error::SetBanner(set_banner_error) => set_banner_error.rejected_message(),
+            _ => None,
+        }
+    }
 }
 
 pub async fn schedule_callback(ctx: serenity_prelude::Context, schedule: Schedule) {
@@ -211,9 +408,16 @@ pub async fn schedule_callback(ctx: serenity_prelude::Context, schedule: Schedul
             )),
         };
 
-        let Err(err) = result else {
-            debug!("Task finished successfully");
-            return;
+        let err = match result {
+            Ok(url) => {
+                debug!("Task finished successfully");
+                webhook::emit(
+                    state.reqw_client(),
+                    Event::new(EventKind::BannerChanged, &schedule).url(&url),
+                );
+                return;
+            }
+            Err(err) => err,
         };
 
         error!("Task had an error: {err:?}");
@@ -232,11 +436,29 @@ pub async fn schedule_callback(ctx: serenity_prelude::Context, schedule: Schedul
                     }
                     ScheduleAction::RetryNewImage => {
                         if let (Some(url), Some(_)) = err.attempted_url_and_message() {
+                            webhook::emit(
+                                state.reqw_client(),
+                                Event::new(EventKind::ImageRejected, &schedule)
+                                    .url(&url)
+                                    .error(err.source().category()),
+                            );
                             avoid_list.push(url);
                         }
+
+                        let reaction = match err.rejected_message() {
+                            Some(message) => react_to_rejected_image(&ctx, message).await,
+                            None => Ok(()),
+                        };
+                        if let Err(reaction_err) = reaction {
+                            warn!("Could not react to rejected image: {reaction_err}");
+                        }
                     }
                     ScheduleAction::Abort => {
-                        let _ = state.deque(schedule.guild_id()).await;
+                        let _ = state.deque(schedule.key()).await;
+                        webhook::emit(
+                            state.reqw_client(),
+                            Event::new(EventKind::ScheduleAborted, &schedule).error(err.source().category()),
+                        );
                         return;
                     }
                 }
@@ -245,8 +467,22 @@ pub async fn schedule_callback(ctx: serenity_prelude::Context, schedule: Schedul
                 let message = format!("CRITICAL ERROR schedule={schedule:?}: {critical_err:?}");
                 error!(message);
                 // if we encounter an error _now_ it's over anyways
-                let _ = state.deque(schedule.guild_id()).await;
-                let _ = dm_users(&ctx, state.owners(), &message).await;
+                let _ = state.deque(schedule.key()).await;
+                webhook::emit(
+                    state.reqw_client(),
+                    Event::new(EventKind::ScheduleAborted, &schedule).error(critical_err.category()),
+                );
+                let guild_id = schedule.guild_id();
+                let guild_name = format!("{guild_id}: {}", guild_id.name(&ctx.cache).unwrap_or_default());
+                let _ = alert_owners(
+                    &ctx,
+                    state.owners(),
+                    guild_id,
+                    &guild_name,
+                    critical_err.category(),
+                    &message,
+                )
+                .await;
 
                 return;
             }
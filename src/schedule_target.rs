@@ -0,0 +1,205 @@
+//! What a schedule changes
+//!
+//! A guild can rotate its banner, its icon and its splash images,
+//! each from its own channel and at its own pace.
+//! The bot's own avatar and profile banner in that guild work without any boosts.
+//! Every target is a separate schedule, identified by a [ScheduleKey].
+
+use std::{fmt::Display, str::FromStr};
+
+use poise::serenity_prelude::GuildId;
+
+use crate::{
+    constants::{ICON_SIZE, PROFILE_BANNER_HEIGHT, PROFILE_BANNER_WIDTH, SPLASH_HEIGHT, SPLASH_WIDTH},
+    image_pipeline::BannerSize,
+};
+
+/// The image a schedule changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, poise::ChoiceParameter)]
+pub enum ScheduleTarget {
+    #[default]
+    #[name = "Banner"]
+    Banner,
+    #[name = "Icon"]
+    Icon,
+    #[name = "Invite splash"]
+    InviteSplash,
+    #[name = "Discovery splash"]
+    DiscoverySplash,
+    #[name = "Bot avatar"]
+    MemberAvatar,
+    #[name = "Bot banner"]
+    MemberBanner,
+}
+
+/// Whether a target can show animated images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Animation {
+    /// Static images only
+    Never,
+    /// Only on guilds with this feature
+    WithFeature(&'static str),
+    /// On every guild
+    Always,
+}
+
+impl Animation {
+    /// Can the target be animated on a guild? `has_feature` tells if the guild has a feature
+    pub fn allowed(self, has_feature: impl FnOnce(&'static str) -> bool) -> bool {
+        match self {
+            Self::Never => false,
+            Self::WithFeature(feature) => has_feature(feature),
+            Self::Always => true,
+        }
+    }
+}
+
+impl ScheduleTarget {
+    /// Every target there is
+    pub const ALL: [Self; 6] = [
+        Self::Banner,
+        Self::Icon,
+        Self::InviteSplash,
+        Self::DiscoverySplash,
+        Self::MemberAvatar,
+        Self::MemberBanner,
+    ];
+
+    /// Size the image is cropped and resized to. Icons and avatars are always square, splashes are 16:9
+    pub fn size(self) -> BannerSize {
+        match self {
+            Self::Banner => BannerSize::from_settings(),
+            Self::Icon | Self::MemberAvatar => BannerSize {
+                width: ICON_SIZE,
+                height: ICON_SIZE,
+            },
+            Self::InviteSplash | Self::DiscoverySplash => BannerSize {
+                width: SPLASH_WIDTH,
+                height: SPLASH_HEIGHT,
+            },
+            Self::MemberBanner => BannerSize {
+                width: PROFILE_BANNER_WIDTH,
+                height: PROFILE_BANNER_HEIGHT,
+            },
+        }
+    }
+
+    /// Guild feature needed to set this target at all.
+    /// Every guild can have an icon, and the bot can always change its own profile
+    pub fn required_feature(self) -> Option<&'static str> {
+        match self {
+            Self::Banner => Some("BANNER"),
+            Self::Icon | Self::MemberAvatar | Self::MemberBanner => None,
+            Self::InviteSplash => Some("INVITE_SPLASH"),
+            Self::DiscoverySplash => Some("DISCOVERABLE"),
+        }
+    }
+
+    /// Whether the target can show animated images
+    pub fn animation(self) -> Animation {
+        match self {
+            Self::Banner => Animation::WithFeature("ANIMATED_BANNER"),
+            Self::Icon => Animation::WithFeature("ANIMATED_ICON"),
+            Self::InviteSplash | Self::DiscoverySplash => Animation::Never,
+            // bots don't need nitro for animated profiles
+            Self::MemberAvatar | Self::MemberBanner => Animation::Always,
+        }
+    }
+
+    /// How the target is stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Banner => "banner",
+            Self::Icon => "icon",
+            Self::InviteSplash => "invite_splash",
+            Self::DiscoverySplash => "discovery_splash",
+            Self::MemberAvatar => "member_avatar",
+            Self::MemberBanner => "member_banner",
+        }
+    }
+}
+
+impl FromStr for ScheduleTarget {
+    type Err = ScheduleTargetParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "banner" => Ok(Self::Banner),
+            "icon" => Ok(Self::Icon),
+            "invite_splash" => Ok(Self::InviteSplash),
+            "discovery_splash" => Ok(Self::DiscoverySplash),
+            "member_avatar" => Ok(Self::MemberAvatar),
+            "member_banner" => Ok(Self::MemberBanner),
+            other => Err(ScheduleTargetParseError(other.to_owned())),
+        }
+    }
+}
+
+impl Display for ScheduleTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown schedule target: {0}")]
+pub struct ScheduleTargetParseError(String);
+
+/// Identifies a schedule. There is at most one schedule per guild and target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScheduleKey {
+    pub guild_id: GuildId,
+    pub target: ScheduleTarget,
+}
+
+impl ScheduleKey {
+    pub fn new(guild_id: GuildId, target: ScheduleTarget) -> Self {
+        Self { guild_id, target }
+    }
+
+    /// Keys of every target of a guild
+    pub fn all_of_guild(guild_id: GuildId) -> impl Iterator<Item = Self> {
+        ScheduleTarget::ALL
+            .into_iter()
+            .map(move |target| Self::new(guild_id, target))
+    }
+}
+
+/// How the key is stored in the database.
+/// Banner schedules keep the plain guild id, so schedules from older versions keep working
+impl Display for ScheduleKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.target {
+            ScheduleTarget::Banner => write!(f, "{}", self.guild_id),
+            target => write!(f, "{}:{target}", self.guild_id),
+        }
+    }
+}
+
+impl FromStr for ScheduleKey {
+    type Err = ScheduleKeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (guild_id, target) = match s.split_once(':') {
+            Some((guild_id, target)) => (guild_id, target.parse()?),
+            None => (s, ScheduleTarget::Banner),
+        };
+
+        let guild_id = guild_id
+            .parse::<u64>()
+            .ok()
+            .filter(|id| *id != 0)
+            .ok_or_else(|| ScheduleKeyParseError::GuildId(s.to_owned()))?;
+
+        Ok(Self::new(GuildId::new(guild_id), target))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleKeyParseError {
+    #[error("Invalid guild id in schedule key: {0}")]
+    GuildId(String),
+
+    #[error(transparent)]
+    Target(#[from] ScheduleTargetParseError),
+}
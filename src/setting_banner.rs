@@ -1,18 +1,28 @@
 //! This module is for extending the [GuildId](GuildId) struct
-//! with functions for setting the banner from an URL.
-
-use std::collections::HashMap;
+//! with functions for setting the banner, or any other [ScheduleTarget], from an URL.
 
 use bytes::Bytes;
+use image::{ImageError, ImageFormat};
 use poise::serenity_prelude::{
     self, CreateAttachment, EditGuild, GuildId, Http, Message, futures::TryStreamExt,
 };
-use rand::seq::IndexedRandom;
-use reqwest::Client;
-use tracing::{debug, info, instrument};
+use reqwest::{Client, header::CONTENT_TYPE};
+use serde_json::{Value, json};
+use tracing::{debug, info, instrument, warn};
 use url::Url;
 
-use crate::constants::MAXIMUM_IMAGE_SIZE;
+use crate::{
+    caption::Caption,
+    collage::CollageLayout,
+    constants::{MAXIMUM_DOWNLOAD_SIZE, MAXIMUM_IMAGE_SIZE},
+    crop_hint::CropHint,
+    image_pipeline::{self, ProcessOptions, ProcessedImage},
+    localization::Language,
+    media_kind::{self, MediaKind},
+    owner_digest::Severity,
+    schedule::Schedule,
+    schedule_target::ScheduleTarget,
+};
 
 /// Errors possible when setting a banner
 #[derive(Debug, thiserror::Error)]
@@ -26,8 +36,8 @@ pub enum SetBannerError {
     #[error("Could not pick a url. Rng failed")]
     CouldNotPickAUrl,
 
-    #[error("Could not determin file extenstion on image")]
-    CouldNotDeterminFileExtension(Url),
+    #[error("Unknown image format: {} on message: {}", .0, .1.link())]
+    UnknownImageFormat(Url, Box<Message>),
 
     #[error("Missing 'banner' feature")]
     MissingBannerFeature,
@@ -35,6 +45,21 @@ pub enum SetBannerError {
     #[error("Missing 'animated banner' feature: {} on message: {}", .0, .1.link())]
     MissingAnimatedBannerFeature(Url, Box<Message>),
 
+    #[error("Missing 'animated icon' feature: {} on message: {}", .0, .1.link())]
+    MissingAnimatedIconFeature(Url, Box<Message>),
+
+    #[error("Missing 'invite splash' feature")]
+    MissingInviteSplashFeature,
+
+    #[error("Missing 'discoverable' feature")]
+    MissingDiscoverableFeature,
+
+    #[error("Splash images can't be animated: {} on message: {}", .0, .1.link())]
+    AnimatedSplash(Url, Box<Message>),
+
+    #[error("Image is not allowed by the media filter: {} on message: {}", .0, .1.link())]
+    FilteredMediaKind(Url, Box<Message>),
+
     #[error("Image is empty: {} on message: {}", .0, .1.link())]
     ImageIsEmpty(Url, Box<Message>),
 
@@ -46,19 +71,138 @@ pub enum SetBannerError {
 
     #[error("Could not encode image to base64: {} on message: {}", .0, .1.link())]
     Base64Encoding(Url, Box<Message>),
+
+    #[error("Could not crop or resize image: {} on message: {}", .0, .1.link())]
+    ImageProcessing(Url, Box<Message>),
+
+    #[error("Image is corrupt: {} on message: {}", .0, .1.link())]
+    ImageIsCorrupt(Url, Box<Message>),
+}
+
+impl SetBannerError {
+    /// Short name of the kind of error, for webhooks and logs
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Transport(_) => "transport",
+            Self::DiscordApi(_) => "discord_api",
+            Self::CouldNotPickAUrl => "could_not_pick_a_url",
+            Self::UnknownImageFormat(..) => "unknown_image_format",
+            Self::MissingBannerFeature => "missing_banner_feature",
+            Self::MissingAnimatedBannerFeature(..) => "missing_animated_banner_feature",
+            Self::MissingAnimatedIconFeature(..) => "missing_animated_icon_feature",
+            Self::MissingInviteSplashFeature => "missing_invite_splash_feature",
+            Self::MissingDiscoverableFeature => "missing_discoverable_feature",
+            Self::AnimatedSplash(..) => "animated_splash",
+            Self::FilteredMediaKind(..) => "filtered_media_kind",
+            Self::ImageIsEmpty(..) => "image_is_empty",
+            Self::ImageIsTooBig(..) => "image_is_too_big",
+            Self::ImageUnkownSize(..) => "image_unknown_size",
+            Self::Base64Encoding(..) => "base64_encoding",
+            Self::ImageProcessing(..) => "image_processing",
+            Self::ImageIsCorrupt(..) => "image_is_corrupt",
+        }
+    }
+
+    /// The message of the image, if the image itself can't be used.
+    /// Broken, empty or too big files, images that can't be cropped to the target,
+    /// and animated images the guild can't use
+    pub fn rejected_message(&self) -> Option<&Message> {
+        match self {
+            Self::UnknownImageFormat(_, message)
+            | Self::MissingAnimatedBannerFeature(_, message)
+            | Self::MissingAnimatedIconFeature(_, message)
+            | Self::AnimatedSplash(_, message)
+            | Self::ImageIsEmpty(_, message)
+            | Self::ImageIsTooBig(_, message)
+            | Self::ImageProcessing(_, message)
+            | Self::ImageIsCorrupt(_, message) => Some(&**message),
+            _ => None,
+        }
+    }
+
+    /// How urgently the bot owners need to know about this error
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::DiscordApi(_) => Severity::Error,
+            Self::Transport(_)
+            | Self::CouldNotPickAUrl
+            | Self::MissingBannerFeature
+            | Self::MissingInviteSplashFeature
+            | Self::MissingDiscoverableFeature => Severity::Warning,
+            // another image is picked, and the guild is told if it needs to do something
+            Self::UnknownImageFormat(..)
+            | Self::MissingAnimatedBannerFeature(..)
+            | Self::MissingAnimatedIconFeature(..)
+            | Self::AnimatedSplash(..)
+            | Self::FilteredMediaKind(..)
+            | Self::ImageIsEmpty(..)
+            | Self::ImageIsTooBig(..)
+            | Self::ImageUnkownSize(..)
+            | Self::Base64Encoding(..)
+            | Self::ImageProcessing(..)
+            | Self::ImageIsCorrupt(..) => Severity::Info,
+        }
+    }
+}
+
+/// Does the media filter of the schedule allow the downloaded image? Returns its kind if it does
+///
+/// The index only guesses the kind from the url or content type, which is unknown for webp or links without
+/// an extension.
+fn check_media_filter(
+    bytes: &[u8],
+    schedule: &Schedule,
+    url: &Url,
+    message: &Message,
+) -> Result<Option<MediaKind>, SetBannerError> {
+    let kind = MediaKind::sniff(bytes);
+    if !schedule.media_filter().allows(kind) {
+        debug!("{url} is {kind:?}, which the media filter does not allow");
+        return Err(SetBannerError::FilteredMediaKind(
+            url.clone(),
+            Box::new(message.clone()),
+        ));
+    }
+
+    Ok(kind)
 }
 
 /// Trait for setting a banner from an url
 pub(crate) trait BannerFromUrl {
-    /// Given an [Url](Url) to an image, set the guild banner
-    /// This will download the image into memory,
+    /// Given an [Url](Url) to an image, set the guild banner or icon
+    /// This will download the image into memory, crop and resize it to the target's size,
     /// convert the bytes to base64 and then send it to discord
+    ///
+    /// The `schedule` decides how the image is processed and what it is set as.
+    /// `animated_allowed` comes from [check_target_features], which has to pass before calling this.
+    /// The caption is written in `language`.
+    #[allow(clippy::too_many_arguments)]
     async fn set_banner_from_url_and_message(
         &mut self,
         http: impl AsRef<Http> + Sync + Send + 'static,
         reqw_client: &Client,
         url: &Url,
         message: &Message,
+        schedule: &Schedule,
+        animated_allowed: bool,
+        crop_hint: Option<CropHint>,
+        language: Language,
+    ) -> Result<(), SetBannerError>;
+
+    /// Same as [Self::set_banner_from_url_and_message], but tiles all images into one banner
+    /// following the `layout`. Animated images are turned into a still frame.
+    /// `crop_hints` go with the `media` of the same index.
+    /// [check_target_features] has to pass before calling this.
+    #[allow(clippy::too_many_arguments)]
+    async fn set_collage_from_urls_and_messages(
+        &mut self,
+        http: impl AsRef<Http> + Sync + Send + 'static,
+        reqw_client: &Client,
+        media: &[(Url, Message)],
+        layout: CollageLayout,
+        schedule: &Schedule,
+        crop_hints: &[Option<CropHint>],
+        language: Language,
     ) -> Result<(), SetBannerError>;
 }
 
@@ -70,163 +214,320 @@ impl BannerFromUrl for GuildId {
         reqw_client: &Client,
         url: &Url,
         message: &Message,
+        schedule: &Schedule,
+        animated_allowed: bool,
+        crop_hint: Option<CropHint>,
+        language: Language,
     ) -> Result<(), SetBannerError> {
-        let extension = url
-            .path()
-            .split('.')
-            .next_back()
-            .ok_or_else(|| SetBannerError::CouldNotDeterminFileExtension(url.clone()))?;
-
-        debug!("Found extension: {extension}");
-        // Disable banner feature check when in dev environment
-        #[cfg(not(feature = "dev"))]
-        {
-            use serenity_prelude::small_fixed_array::FixedString;
-
-            let guild = self.to_partial_guild(http.as_ref()).await?;
-            let features = guild.features;
-
-            if !features.contains(&FixedString::from_static_trunc("BANNER")) {
-                return Err(SetBannerError::MissingBannerFeature);
+        let target = schedule.target();
+
+        let (image_bytes, format) = download_image(reqw_client, url, message).await?;
+        let downloaded_size = image_bytes.len();
+
+        // the url might lie about what it is, the bytes don't
+        let kind = check_media_filter(&image_bytes, schedule, url, message)?;
+        let is_animated = kind == Some(MediaKind::Animated);
+        // collage tiles use the first frame if none is set. same for a collage that fell back to a single image
+        let configured_frame = schedule
+            .still_frame()
+            .map(|frame| frame.get() as usize - 1)
+            .or(schedule.collage().map(|_| 0));
+        let still_frame = match (is_animated && !animated_allowed, configured_frame) {
+            (false, _) => None,
+            (true, Some(still_frame)) => {
+                debug!("using frame {} as a static {target}", still_frame + 1);
+                Some(still_frame)
             }
-
-            if extension.to_lowercase() == "gif"
-                && !features.contains(&FixedString::from_static_trunc("ANIMATED_BANNER"))
-            {
-                return Err(SetBannerError::MissingAnimatedBannerFeature(
-                    url.clone(),
-                    Box::new(message.clone()),
-                ));
+            (true, None) => {
+                let (url, message) = (url.clone(), Box::new(message.clone()));
+                return Err(match target {
+                    ScheduleTarget::Banner => SetBannerError::MissingAnimatedBannerFeature(url, message),
+                    ScheduleTarget::Icon => SetBannerError::MissingAnimatedIconFeature(url, message),
+                    // the bot's profile can always be animated, so only splashes end up here
+                    _ => SetBannerError::AnimatedSplash(url, message),
+                });
             }
-        }
+        };
 
-        // discord cdn has a few image parameters and we can limit the size here
-        // hopefully preventing us from runnig into the 10mb limit.
-        // the only thing thats a little wierd is having to save the ex, is and hm
-        // query parameters. oh well.
-        let mut query_params = HashMap::with_capacity(3);
-        for (key, value) in url
-            .query_pairs()
-            .filter(|(key, _)| matches!(key.as_bytes(), b"ex" | b"is" | b"hm"))
-        {
-            query_params.insert(key, value);
-        }
+        // cropping and resizing happens locally, so it works for images outside of the discord cdn too
+        debug!("processing image");
+        let caption = schedule
+            .caption()
+            .map(|position| Caption::credit(language, &[&*message.author.name], position));
+        let options = ProcessOptions::new(target.size(), MAXIMUM_IMAGE_SIZE)
+            .still_frame(still_frame)
+            .caption(caption)
+            .crop_hint(crop_hint);
+        let processed =
+            tokio::task::spawn_blocking(move || image_pipeline::process(&image_bytes, format, options))
+                .await
+                .map_err(|_| SetBannerError::ImageProcessing(url.clone(), Box::new(message.clone())))?
+                .map_err(|err| pipeline_error(err, url, message))?;
+
+        info!(
+            "Image {url} went from {downloaded_size} to {} bytes",
+            processed.bytes.len()
+        );
+
+        upload(http.as_ref(), *self, target, processed, url, message).await?;
+
+        info!("Guild {} changed its {target} to: {}", self.get(), url.as_str());
 
-        let mut url = url.clone();
-        url.query_pairs_mut()
-            .clear()
-            .extend_pairs(query_params.iter())
-            .append_pair("width", "960")
-            .append_pair("height", "540")
-            .finish();
-
-        debug!("requesting image");
-        let response = reqw_client.get(url.as_ref()).send().await?;
-
-        debug!("checking image size");
-        // check content length header
-        let estimated_content_length = match response
-            .content_length()
-            .map(|len| usize::try_from(len).unwrap_or(usize::MAX))
-        {
-            Some(0) => {
-                return Err(SetBannerError::ImageIsEmpty(
-                    url.clone(),
-                    Box::new(message.clone()),
-                ));
-            }
-            Some(MAXIMUM_IMAGE_SIZE..) => {
-                return Err(SetBannerError::ImageIsTooBig(
-                    url.clone(),
-                    Box::new(message.clone()),
-                ));
-            }
-            // instead of failing, return the maximum size in hopes of it working out.
-            // worst case, we've just allocated a few mb of memory that won't be used... oh well
-            None => MAXIMUM_IMAGE_SIZE,
-            Some(len) => len,
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn set_collage_from_urls_and_messages(
+        &mut self,
+        http: impl AsRef<Http> + Sync + Send + 'static,
+        reqw_client: &Client,
+        media: &[(Url, Message)],
+        layout: CollageLayout,
+        schedule: &Schedule,
+        crop_hints: &[Option<CropHint>],
+        language: Language,
+    ) -> Result<(), SetBannerError> {
+        let Some((first_url, first_message)) = media.first() else {
+            return Err(SetBannerError::CouldNotPickAUrl);
         };
 
-        // Use stream to get image bytes because if using response.bytes()
-        // there would be a risk of downloading huuuuuge files into RAM if for example
-        // the content_length header would be spoofed.
-        // To give me some headroom
-
-        debug!("fetching image");
-        let (image_bytes, _): (Vec<u8>, Url) = response
-            .bytes_stream()
-            .map_err(SetBannerError::Transport)
-            .try_fold(
-                (Vec::<u8>::with_capacity(estimated_content_length), url.clone()),
-                |(mut acc, url), value: Bytes| async move {
-                    if acc.len() + value.len() > MAXIMUM_IMAGE_SIZE {
-                        return Err(SetBannerError::ImageIsTooBig(url, Box::new(message.clone())));
-                    }
-
-                    acc.extend_from_slice(&value);
-                    Ok((acc, url))
-                },
-            )
-            .await?;
-
-        // check actual content length
-        match image_bytes.len() {
-            0 => return Err(SetBannerError::ImageIsEmpty(url, Box::new(message.clone()))),
-            MAXIMUM_IMAGE_SIZE.. => {
-                return Err(SetBannerError::ImageIsTooBig(url, Box::new(message.clone())));
-            }
-            _ => {}
+        let target = schedule.target();
+
+        let mut images = Vec::with_capacity(media.len());
+        for ((url, message), crop_hint) in media.iter().zip(crop_hints) {
+            let (bytes, format) = download_image(reqw_client, url, message).await?;
+            check_media_filter(&bytes, schedule, url, message)?;
+            images.push((bytes, format, crop_hint.unwrap_or_default()));
         }
 
-        let image_bytes_2 = CreateAttachment::bytes(image_bytes, "");
-        let image = image_bytes_2
-            .encode(&format!("image/{extension}"))
-            .await
-            .map_err(|_| SetBannerError::Base64Encoding(url.clone(), Box::new(message.clone())))?;
-
-        let edit_guild = {
-            #[cfg(feature = "dev")]
-            {
-                debug!("Setting icon");
-                EditGuild::new().icon(Some(image))
+        let mut authors: Vec<&str> = Vec::with_capacity(media.len());
+        for (_, message) in media {
+            if !authors.contains(&&*message.author.name) {
+                authors.push(&message.author.name);
             }
+        }
 
-            #[cfg(not(feature = "dev"))]
-            {
-                debug!("Setting banner");
-                EditGuild::new().banner(Some(image))
+        debug!("processing {} images into a collage", images.len());
+        let still_frame = schedule.still_frame().map_or(0, |frame| frame.get() as usize - 1);
+        let caption = schedule
+            .caption()
+            .map(|position| Caption::credit(language, &authors, position));
+        let options = ProcessOptions::new(target.size(), MAXIMUM_IMAGE_SIZE).caption(caption);
+
+        // decoding errors are blamed on the image that caused them, everything else on the first one
+        let processed = tokio::task::spawn_blocking(move || {
+            let mut decoded = Vec::with_capacity(images.len());
+            for (index, (bytes, format, crop_hint)) in images.iter().enumerate() {
+                let image =
+                    image_pipeline::decode_still(bytes, *format, still_frame).map_err(|err| (index, err))?;
+                decoded.push((image, *crop_hint));
             }
-        };
-
-        self.edit(http.as_ref(), edit_guild).await?;
 
-        info!("Guild {} changed to: {}", self.get(), url.as_str());
+            image_pipeline::process_collage(decoded, layout, options).map_err(|err| (0, err))
+        })
+        .await
+        .map_err(|_| SetBannerError::ImageProcessing(first_url.clone(), Box::new(first_message.clone())))?
+        .map_err(|(index, err)| {
+            let (url, message) = &media[index];
+            pipeline_error(err, url, message)
+        })?;
+
+        info!(
+            "Collage of {} images is {} bytes",
+            media.len(),
+            processed.bytes.len()
+        );
+
+        upload(http.as_ref(), *self, target, processed, first_url, first_message).await?;
+
+        let urls: Vec<&str> = media.iter().map(|(url, _)| url.as_str()).collect();
+        info!(
+            "Guild {} changed its {target} to a collage of: {}",
+            self.get(),
+            urls.join(", ")
+        );
 
         Ok(())
     }
 }
 
-pub(crate) trait RandomBanner: BannerFromUrl {
-    /// Given a slice of [Url](Url), pick a random entry
-    /// and try and set it as the guild banner
-    ///
-    /// Returns Ok(url) with the url being choosen
-    #[instrument(skip_all)]
-    async fn set_random_banner_with_message<'url>(
-        &mut self,
-        http: impl AsRef<Http> + Sync + Send + 'static,
-        reqw_client: &Client,
-        urls: &'url [(Url, Message)],
-    ) -> Result<&'url Url, SetBannerError> {
-        let (url, message) = urls
-            .choose(&mut rand::rng())
-            .ok_or(SetBannerError::CouldNotPickAUrl)?;
+/// Make sure the guild can have the target at all. Returns whether it can be animated
+///
+/// Always allowed when in dev environment, unless the target can't be animated at all
+pub(crate) async fn check_target_features(
+    http: &Http,
+    guild_id: GuildId,
+    target: ScheduleTarget,
+) -> Result<bool, SetBannerError> {
+    #[cfg(not(feature = "dev"))]
+    {
+        use serenity_prelude::small_fixed_array::FixedString;
+
+        let guild = guild_id.to_partial_guild(http).await?;
+        let features = guild.features;
+
+        let has = |feature: &'static str| features.contains(&FixedString::from_static_trunc(feature));
+
+        if target.required_feature().is_some_and(|required| !has(required)) {
+            return Err(match target {
+                ScheduleTarget::InviteSplash => SetBannerError::MissingInviteSplashFeature,
+                ScheduleTarget::DiscoverySplash => SetBannerError::MissingDiscoverableFeature,
+                _ => SetBannerError::MissingBannerFeature,
+            });
+        }
+
+        Ok(target.animation().allowed(has))
+    }
 
-        self.set_banner_from_url_and_message(http, reqw_client, url, message)
-            .await?;
+    #[cfg(feature = "dev")]
+    {
+        use crate::schedule_target::Animation;
 
-        Ok(url)
+        let _ = (http, guild_id);
+        Ok(target.animation() != Animation::Never)
     }
 }
 
-impl<T> RandomBanner for T where T: BannerFromUrl {}
+/// Download an image into memory and find out its format
+async fn download_image(
+    reqw_client: &Client,
+    url: &Url,
+    message: &Message,
+) -> Result<(Vec<u8>, ImageFormat), SetBannerError> {
+    let url = url.clone();
+
+    debug!("requesting image");
+    let response = reqw_client.get(url.as_ref()).send().await?;
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    debug!("checking image size");
+    // check content length header
+    let estimated_content_length = match response
+        .content_length()
+        .map(|len| usize::try_from(len).unwrap_or(usize::MAX))
+    {
+        Some(0) => {
+            return Err(SetBannerError::ImageIsEmpty(
+                url.clone(),
+                Box::new(message.clone()),
+            ));
+        }
+        Some(MAXIMUM_DOWNLOAD_SIZE..) => {
+            return Err(SetBannerError::ImageIsTooBig(
+                url.clone(),
+                Box::new(message.clone()),
+            ));
+        }
+        // instead of failing, return the maximum size in hopes of it working out.
+        // worst case, we've just allocated a few mb of memory that won't be used... oh well
+        None => MAXIMUM_IMAGE_SIZE,
+        Some(len) => len,
+    };
+
+    // Use stream to get image bytes because if using response.bytes()
+    // there would be a risk of downloading huuuuuge files into RAM if for example
+    // the content_length header would be spoofed.
+    // To give me some headroom
+
+    debug!("fetching image");
+    let (image_bytes, _): (Vec<u8>, Url) = response
+        .bytes_stream()
+        .map_err(SetBannerError::Transport)
+        .try_fold(
+            (Vec::<u8>::with_capacity(estimated_content_length), url.clone()),
+            |(mut acc, url), value: Bytes| async move {
+                if acc.len() + value.len() > MAXIMUM_DOWNLOAD_SIZE {
+                    return Err(SetBannerError::ImageIsTooBig(url, Box::new(message.clone())));
+                }
+
+                acc.extend_from_slice(&value);
+                Ok((acc, url))
+            },
+        )
+        .await?;
+
+    // check actual content length
+    match image_bytes.len() {
+        0 => return Err(SetBannerError::ImageIsEmpty(url, Box::new(message.clone()))),
+        MAXIMUM_DOWNLOAD_SIZE.. => {
+            return Err(SetBannerError::ImageIsTooBig(url, Box::new(message.clone())));
+        }
+        _ => {}
+    }
+
+    // neither the url nor the content type are reliable, so look at the bytes first
+    let format = media_kind::detect_format(&image_bytes, content_type.as_deref())
+        .ok_or_else(|| SetBannerError::UnknownImageFormat(url.clone(), Box::new(message.clone())))?;
+    debug!("Detected format: {}", format.to_mime_type());
+
+    Ok((image_bytes, format))
+}
+
+/// Which image went wrong and how
+fn pipeline_error(err: ImageError, url: &Url, message: &Message) -> SetBannerError {
+    match err {
+        // the whole image is decoded, so truncated and broken files end up here
+        ImageError::Decoding(_) | ImageError::IoError(_) => {
+            warn!("Could not decode image {url}: {err}");
+            SetBannerError::ImageIsCorrupt(url.clone(), Box::new(message.clone()))
+        }
+        err => {
+            warn!("Could not process image {url}: {err}");
+            SetBannerError::ImageProcessing(url.clone(), Box::new(message.clone()))
+        }
+    }
+}
+
+/// Send the processed image to discord as the target. `url` and `message` are blamed if it fails
+async fn upload(
+    http: &Http,
+    guild_id: GuildId,
+    target: ScheduleTarget,
+    processed: ProcessedImage,
+    url: &Url,
+    message: &Message,
+) -> Result<(), SetBannerError> {
+    // the pipeline tried its best to shrink it, but some images just won't fit
+    if processed.bytes.len() >= MAXIMUM_IMAGE_SIZE {
+        return Err(SetBannerError::ImageIsTooBig(
+            url.clone(),
+            Box::new(message.clone()),
+        ));
+    }
+
+    let image_bytes_2 = CreateAttachment::bytes(processed.bytes, "");
+    let image = image_bytes_2
+        .encode(processed.format.to_mime_type())
+        .await
+        .map_err(|_| SetBannerError::Base64Encoding(url.clone(), Box::new(message.clone())))?;
+
+    debug!("Setting {target}");
+    let edit_guild = match target {
+        ScheduleTarget::Banner => EditGuild::new().banner(Some(image)),
+        ScheduleTarget::Icon => EditGuild::new().icon(Some(image)),
+        ScheduleTarget::InviteSplash => EditGuild::new().splash(Some(image)),
+        ScheduleTarget::DiscoverySplash => EditGuild::new().discovery_splash(Some(image)),
+        ScheduleTarget::MemberAvatar => {
+            return edit_current_member(http, guild_id, json!({ "avatar": image })).await;
+        }
+        ScheduleTarget::MemberBanner => {
+            return edit_current_member(http, guild_id, json!({ "banner": image })).await;
+        }
+    };
+
+    guild_id.edit(http, edit_guild).await?;
+
+    Ok(())
+}
+
+/// Change the bot's own member profile in the guild, instead of the guild itself.
+/// There is no builder for the avatar and banner of the current member, so the json is sent as is
+async fn edit_current_member(http: &Http, guild_id: GuildId, profile: Value) -> Result<(), SetBannerError> {
+    http.edit_current_member(guild_id, &profile, None).await?;
+
+    Ok(())
+}
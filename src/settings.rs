@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     str::FromStr,
     sync::OnceLock,
@@ -8,7 +9,7 @@ use config::{Config, ConfigError};
 use poise::serenity_prelude::Token;
 use serde::Deserialize;
 
-use crate::interval::Interval;
+use crate::{interval::Interval, owner_digest::Severity};
 
 static SETTINGS: OnceLock<Settings> = OnceLock::new();
 
@@ -19,12 +20,33 @@ pub enum SettingsError {
 }
 
 /// Wrapper for all settings
+///
+/// Settings added after the first release have defaults, so older settings files keep working.
+/// The defaults are the same as in `settings.template.toml`
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     /// Bot settings
     pub bot: Bot,
     /// Scheduler settings
     pub scheduler: Scheduler,
+    /// Deduplication settings
+    #[serde(default)]
+    pub deduplication: Deduplication,
+    /// Image pipeline settings
+    #[serde(default)]
+    pub image: Image,
+    /// Caption settings
+    #[serde(default)]
+    pub caption: Caption,
+    /// Webhook settings
+    #[serde(default)]
+    pub webhook: Webhook,
+    /// Settings for telling the bot owners about errors
+    #[serde(default)]
+    pub owner_notifications: OwnerNotifications,
+    /// Reaction settings
+    #[serde(default)]
+    pub reactions: Reactions,
     /// Database settings
     pub database: Database,
 }
@@ -85,6 +107,166 @@ pub struct Scheduler {
     pub default_message_limit: u32,
     /// Maximum amount of messages to look back for
     pub maximum_message_limit: u32,
+    /// Maximum amount of days to look back for
+    #[serde(default = "default_maximum_lookback_days")]
+    pub maximum_lookback_days: u32,
+    /// Maximum amount of messages to look back for, when looking back by days
+    #[serde(default = "default_lookback_message_cap")]
+    pub lookback_message_cap: u32,
+    /// Maximum amount of threads to look at when the channel is a forum
+    #[serde(default = "default_maximum_forum_threads")]
+    pub maximum_forum_threads: usize,
+    /// Maximum amount of messages to look back for in each forum thread
+    #[serde(default = "default_maximum_messages_per_thread")]
+    pub maximum_messages_per_thread: usize,
+}
+
+fn default_maximum_lookback_days() -> u32 {
+    365
+}
+
+fn default_lookback_message_cap() -> u32 {
+    5000
+}
+
+fn default_maximum_forum_threads() -> usize {
+    50
+}
+
+fn default_maximum_messages_per_thread() -> usize {
+    20
+}
+
+/// Deduplication settings
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Deduplication {
+    /// Download and hash images when they are indexed
+    pub enabled: bool,
+    /// Also compare images by how they look
+    pub perceptual: bool,
+    /// How many bits two perceptual hashes may differ by to count as the same image
+    pub perceptual_distance: u32,
+    /// How many images are downloaded for hashing at the same time, across all channels
+    pub concurrent_downloads: usize,
+}
+
+impl Default for Deduplication {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            perceptual: false,
+            perceptual_distance: 4,
+            concurrent_downloads: 4,
+        }
+    }
+}
+
+/// Image pipeline settings
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Image {
+    /// Width of the banner in pixels
+    pub width: u32,
+    /// Height of the banner in pixels
+    pub height: u32,
+    /// How many days an image that could not be decoded is skipped
+    pub corrupt_image_days: u64,
+}
+
+impl Default for Image {
+    fn default() -> Self {
+        Self {
+            width: 960,
+            height: 540,
+            corrupt_image_days: 7,
+        }
+    }
+}
+
+/// Caption settings
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Caption {
+    /// Height of the text in pixels, on a banner of full size
+    pub font_size: f32,
+    /// 0 is invisible, 1 is fully opaque
+    pub opacity: f32,
+}
+
+impl Default for Caption {
+    fn default() -> Self {
+        Self {
+            font_size: 18.0,
+            opacity: 0.85,
+        }
+    }
+}
+
+/// Webhook settings
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Webhook {
+    /// Url that receives the events of every guild. Empty turns it off
+    pub url: String,
+    /// Secret the payloads are signed with
+    pub secret: String,
+    /// How often a failed delivery is retried
+    pub retries: u32,
+    /// Urls that receive the events of a single guild, keyed by guild id
+    pub guilds: HashMap<String, String>,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            secret: String::new(),
+            retries: 3,
+            guilds: HashMap::new(),
+        }
+    }
+}
+
+/// Settings for telling the bot owners about errors
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OwnerNotifications {
+    /// Errors at least this severe are sent right away
+    pub immediate: Severity,
+    /// Errors less severe than this are only logged
+    pub minimum: Severity,
+    /// Minutes between digests of the errors in between
+    pub digest_interval: u64,
+    /// Minutes before the same kind of error in the same guild is sent right away again
+    pub alert_cooldown: u64,
+}
+
+impl Default for OwnerNotifications {
+    fn default() -> Self {
+        Self {
+            immediate: Severity::Critical,
+            minimum: Severity::Warning,
+            digest_interval: 1440,
+            alert_cooldown: 60,
+        }
+    }
+}
+
+/// Reaction settings
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Reactions {
+    /// Emoji for messages whose image can't be used. Empty turns it off
+    pub rejected_image: String,
+}
+
+impl Default for Reactions {
+    fn default() -> Self {
+        Self {
+            rejected_image: "🚫".to_string(),
+        }
+    }
 }
 
 /// Database settings
@@ -7,7 +7,15 @@ use poise::{
 };
 use tracing::{debug, error, info, instrument};
 
-use crate::{Error, schedule_runner::schedule_callback, settings::Settings, state::State, utils::dm_users};
+use crate::{
+    Error,
+    indexing::catch_up_in_background,
+    owner_digest::{report_suppressed_alerts, send_digests},
+    schedule_runner::schedule_callback,
+    settings::Settings,
+    state::State,
+    utils::dm_users,
+};
 
 #[instrument(skip_all)]
 pub(crate) async fn handle_event_ready(ctx: serenity_prelude::Context, _: &Ready) -> Result<(), Error> {
@@ -36,6 +44,14 @@ pub(crate) async fn handle_event_ready(ctx: serenity_prelude::Context, _: &Ready
         .expect("run only once");
 
     state.load_schedules_from_db().await?;
+    catch_up_in_background(
+        ctx.http.clone(),
+        state.database().clone(),
+        state.reqw_client().clone(),
+    );
+
+    tokio::spawn(send_digests(ctx.clone()));
+    tokio::spawn(report_suppressed_alerts(ctx.clone()));
 
     // Notify that we're ready
     let bot_ready = "Bot ready!";
@@ -10,8 +10,10 @@ use tracing::{info, instrument};
 use crate::{
     Error, Settings,
     constants::USER_AGENT,
-    database::{Database, guild_schedule::GuildSchedule},
+    database::{Database, guild_schedule::GuildSchedule, guild_settings::GuildSettings},
+    owner_digest::{AlertLimiter, OwnerDigest},
     schedule::Schedule,
+    schedule_target::ScheduleKey,
 };
 
 /// The User data struct used in poise
@@ -26,6 +28,10 @@ pub struct State {
     shutdown_messenger: Sender<()>,
     /// Owners
     owners: OnceLock<HashSet<UserId>>,
+    /// Owner notifications waiting for the next digest
+    owner_digest: OwnerDigest,
+    /// Keeps owner alerts from repeating
+    alert_limiter: AlertLimiter,
 }
 
 impl State {
@@ -49,6 +55,8 @@ impl State {
             database,
             shutdown_messenger,
             owners: OnceLock::default(),
+            owner_digest: OwnerDigest::default(),
+            alert_limiter: AlertLimiter::default(),
         })
     }
 
@@ -70,7 +78,9 @@ impl State {
         // insert into db here to make sure we don't loose it if we have to restart the bot
         // but the start_at time has not been reached yet
         let db_schedule = GuildSchedule::from(schedule.clone());
-        self.database.insert(&db_schedule, db_schedule.guild_id()).await?;
+        self.database
+            .insert(&db_schedule, db_schedule.key().to_string())
+            .await?;
 
         self.repeater_handle
             .get()
@@ -80,26 +90,38 @@ impl State {
             .map_err(|err| Error::Scheduler { msg: err.to_string() })
     }
 
-    /// Dequeue a guild
+    /// Dequeue a schedule
     ///
     /// # Panics
     /// Will panic if called before initialization is complete
     #[instrument(skip_all)]
-    pub async fn deque(&self, guild_id: GuildId) -> Result<Schedule, Error> {
-        info!("Removing {guild_id:?}");
+    pub async fn deque(&self, key: ScheduleKey) -> Result<Schedule, Error> {
+        info!("Removing {key:?}");
         self.repeater_handle
             .get()
             .unwrap()
-            .remove(guild_id)
+            .remove(key)
             .await
             .map_err(|err| Error::Scheduler { msg: err.to_string() })?;
         Ok(self
             .database
-            .delete::<GuildSchedule>(guild_id.get())
+            .delete::<GuildSchedule>(key.to_string())
             .await?
             .into())
     }
 
+    /// Dequeue every schedule of a guild. Returns the removed schedules
+    ///
+    /// # Panics
+    /// Will panic if called before initialization is complete
+    pub async fn deque_guild(&self, guild_id: GuildId) -> Result<Vec<Schedule>, Error> {
+        let mut removed = Vec::with_capacity(1);
+        for schedule in self.get_schedules_of_guild(guild_id).await? {
+            removed.push(self.deque(schedule.key()).await?);
+        }
+        Ok(removed)
+    }
+
     /// Load all schedules from the database into the repeater
     ///
     /// # Panics
@@ -109,26 +131,34 @@ impl State {
         // clear everything
         let _ = self.repeater_handle.get().unwrap().clear().await;
 
-        let known_guild_ids: Vec<u64> = self.database().active_schedules().await?;
-        info!("There are {} active schedules stored", known_guild_ids.len());
+        let known_ids: Vec<String> = self.database().active_schedules().await?;
+        info!("There are {} active schedules stored", known_ids.len());
 
         let mut result = LoadFromDbResult::default();
 
-        for id in known_guild_ids {
-            let entry = match self.database().get::<GuildSchedule>(id).await {
+        for id in known_ids {
+            if let Err(err) = id.parse::<ScheduleKey>() {
+                result.failed.push((
+                    id,
+                    RedisError::new(fred::error::ErrorKind::Parse, err.to_string()),
+                ));
+                continue;
+            }
+
+            let entry = match self.database().get::<GuildSchedule>(id.as_str()).await {
                 Ok(Some(entry)) => entry,
                 Ok(None) => {
                     result.failed.push((
-                        GuildId::new(id),
+                        id,
                         RedisError::new(
                             fred::error::ErrorKind::NotFound,
-                            "schedule with guild id was not found",
+                            "schedule with this key was not found",
                         ),
                     ));
                     continue;
                 }
                 Err(err) => {
-                    result.failed.push((GuildId::new(id), err));
+                    result.failed.push((id, err));
                     continue;
                 }
             };
@@ -157,12 +187,34 @@ impl State {
         &self.database
     }
 
-    /// Get the schedule for the guild
-    pub async fn get_schedule(&self, guild_id: GuildId) -> Result<Option<Schedule>, RedisError> {
-        let db_entry = self.database.get::<GuildSchedule>(guild_id.get()).await?;
+    /// Get the schedule with this key
+    pub async fn get_schedule(&self, key: ScheduleKey) -> Result<Option<Schedule>, RedisError> {
+        let db_entry = self.database.get::<GuildSchedule>(key.to_string()).await?;
         Ok(db_entry.map(Into::into))
     }
 
+    /// Get every schedule of the guild, one per target at most
+    pub async fn get_schedules_of_guild(&self, guild_id: GuildId) -> Result<Vec<Schedule>, RedisError> {
+        let mut schedules = Vec::with_capacity(1);
+        for key in ScheduleKey::all_of_guild(guild_id) {
+            if let Some(schedule) = self.get_schedule(key).await? {
+                schedules.push(schedule);
+            }
+        }
+        Ok(schedules)
+    }
+
+    /// Get the settings of the guild. Guilds that never changed any get the defaults
+    pub async fn guild_settings(&self, guild_id: GuildId) -> Result<GuildSettings, RedisError> {
+        let settings = self.database.get::<GuildSettings>(guild_id.get()).await?;
+        Ok(settings.unwrap_or_else(|| GuildSettings::new(guild_id)))
+    }
+
+    /// Store the settings of a guild
+    pub async fn set_guild_settings(&self, settings: &GuildSettings) -> Result<(), RedisError> {
+        self.database.insert(settings, settings.guild_id().get()).await
+    }
+
     /// Get a clone of the repeater handle
     ///
     /// # Panics
@@ -191,12 +243,22 @@ impl State {
     pub fn set_owners(&self, owners: HashSet<UserId>) -> Result<(), HashSet<UserId>> {
         self.owners.set(owners)
     }
+
+    /// Owner notifications waiting for the next digest
+    pub fn owner_digest(&self) -> &OwnerDigest {
+        &self.owner_digest
+    }
+
+    /// Keeps owner alerts from repeating
+    pub fn alert_limiter(&self) -> &AlertLimiter {
+        &self.alert_limiter
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct LoadFromDbResult {
     successful: Vec<GuildSchedule>,
-    failed: Vec<(GuildId, RedisError)>,
+    failed: Vec<(String, RedisError)>,
 }
 
 impl Display for LoadFromDbResult {
@@ -207,8 +269,8 @@ impl Display for LoadFromDbResult {
         }
 
         writeln!(f, "Failed:")?;
-        for (guild_id, err) in &self.failed {
-            writeln!(f, "{guild_id}, {err:#?}")?;
+        for (id, err) in &self.failed {
+            writeln!(f, "{id}, {err:#?}")?;
         }
 
         Ok(())
@@ -0,0 +1,365 @@
+//! Webhook events for dashboards and the like
+//!
+//! Events are posted as json to the urls in the `[webhook]` settings.
+//! Every payload is signed with HMAC-SHA256 using the configured secret.
+//! The signature is sent hex encoded in the [SIGNATURE_HEADER] header as `sha256=...`.
+
+pub mod receiver;
+
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Response, header::CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{Settings, schedule::Schedule, utils::current_unix_timestamp};
+
+/// Header with the signature of the payload
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Header with the kind of event, same as [Event::event]
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+/// What happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// A schedule changed the image
+    BannerChanged,
+    /// Someone started a schedule
+    ScheduleStarted,
+    /// Someone stopped a schedule, or its channel or guild is gone
+    ScheduleStopped,
+    /// A schedule ran into an error it can't recover from
+    ScheduleAborted,
+    /// An image could not be used and another one is picked
+    ImageRejected,
+}
+
+impl EventKind {
+    /// Same as the serialized form
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::BannerChanged => "banner_changed",
+            Self::ScheduleStarted => "schedule_started",
+            Self::ScheduleStopped => "schedule_stopped",
+            Self::ScheduleAborted => "schedule_aborted",
+            Self::ImageRejected => "image_rejected",
+        }
+    }
+}
+
+/// The payload of a webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub event: EventKind,
+    /// Ids are strings, so receivers don't lose precision on 64 bit numbers
+    pub guild_id: String,
+    /// Channel the schedule picks images from
+    pub channel_id: String,
+    /// What the schedule changes. See [ScheduleTarget](crate::schedule_target::ScheduleTarget)
+    pub target: String,
+    /// The image, for changes and rejected images
+    pub url: Option<String>,
+    /// What went wrong, for aborted schedules and rejected images. See [crate::Error::category]
+    pub error: Option<String>,
+    /// Unix timestamp of when the event happened
+    pub timestamp: u64,
+}
+
+impl Event {
+    pub fn new(event: EventKind, schedule: &Schedule) -> Self {
+        Self {
+            event,
+            guild_id: schedule.guild_id().to_string(),
+            channel_id: schedule.channel_id().to_string(),
+            target: schedule.target().to_string(),
+            url: None,
+            error: None,
+            timestamp: current_unix_timestamp(),
+        }
+    }
+
+    #[must_use]
+    pub fn url(mut self, url: &Url) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn error(mut self, category: &str) -> Self {
+        self.error = Some(category.to_string());
+        self
+    }
+}
+
+/// Send the event to the global url and the url of its guild
+///
+/// Delivery happens in the background, so callers never wait on slow receivers
+pub fn emit(client: &Client, event: Event) {
+    let settings = &Settings::get().webhook;
+
+    let urls: Vec<String> = [
+        Some(settings.url.as_str()),
+        settings.guilds.get(&event.guild_id).map(String::as_str),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|url| !url.is_empty())
+    .map(str::to_owned)
+    .collect();
+
+    if urls.is_empty() {
+        return;
+    }
+
+    let payload = match serde_json::to_vec(&event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            warn!("Could not serialize webhook event {event:?}: {err}");
+            return;
+        }
+    };
+    let signature = sign(&settings.secret, &payload);
+
+    for url in urls {
+        tokio::spawn(deliver(
+            client.clone(),
+            url,
+            event.event,
+            payload.clone(),
+            signature.clone(),
+            settings.retries,
+        ));
+    }
+}
+
+/// Post the payload, retrying with a growing delay until it is accepted or the retries run out
+async fn deliver(
+    client: Client,
+    url: String,
+    event: EventKind,
+    payload: Vec<u8>,
+    signature: String,
+    retries: u32,
+) {
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            sleep(Duration::from_secs(2u64.saturating_pow(attempt).min(60))).await;
+        }
+
+        let result = client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event.as_str())
+            .body(payload.clone())
+            .send()
+            .await
+            .and_then(Response::error_for_status);
+
+        match result {
+            Ok(_) => {
+                debug!("Delivered webhook event {} to {url}", event.as_str());
+                return;
+            }
+            Err(err) => warn!(
+                "Could not deliver webhook event {} to {url}, attempt {} of {}: {err}",
+                event.as_str(),
+                attempt + 1,
+                retries + 1
+            ),
+        }
+    }
+}
+
+/// Signature of a payload, as sent in the [SIGNATURE_HEADER] header
+pub fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(payload);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Is `signature` the signature of the payload? Compares in constant time
+pub fn verify(secret: &str, payload: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature.strip_prefix("sha256=").and_then(decode_hex) else {
+        return false;
+    };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(payload);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Turn hex back into bytes. `None` if it isn't valid hex
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::{
+        Event, EventKind, deliver,
+        receiver::{MAXIMUM_BODY_SIZE, receive},
+        sign, verify,
+    };
+
+    const SECRET: &str = "hunter2";
+    const PAYLOAD: &[u8] = br#"{"event":"banner_changed","guild_id":"1"}"#;
+
+    #[test]
+    fn sign_matches_known_hmac() {
+        // test case 2 of RFC 4231
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn verify_accepts_own_signature() {
+        let signature = sign(SECRET, PAYLOAD);
+        assert!(verify(SECRET, PAYLOAD, &signature));
+    }
+
+    #[test]
+    fn verify_accepts_uppercase_hex() {
+        let signature = sign(SECRET, PAYLOAD).replace("sha256=", "").to_uppercase();
+        assert!(verify(SECRET, PAYLOAD, &format!("sha256={signature}")));
+    }
+
+    #[test]
+    fn verify_rejects_tampered_payload() {
+        let signature = sign(SECRET, PAYLOAD);
+        let tampered = br#"{"event":"banner_changed","guild_id":"2"}"#;
+        assert!(!verify(SECRET, tampered, &signature));
+    }
+
+    #[test]
+    fn verify_rejects_tampered_signature() {
+        let mut signature = sign(SECRET, PAYLOAD);
+        let last = if signature.ends_with('0') { "1" } else { "0" };
+        signature.replace_range(signature.len() - 1.., last);
+        assert!(!verify(SECRET, PAYLOAD, &signature));
+    }
+
+    #[test]
+    fn verify_rejects_wrong_secret() {
+        let signature = sign("not the secret", PAYLOAD);
+        assert!(!verify(SECRET, PAYLOAD, &signature));
+    }
+
+    #[test]
+    fn verify_rejects_malformed_signatures() {
+        let signature = sign(SECRET, PAYLOAD);
+        let hex = signature.strip_prefix("sha256=").unwrap();
+
+        for malformed in [
+            "",
+            "sha256=",
+            hex,
+            &format!("sha1={hex}"),
+            &format!("sha256={}", &hex[1..]),
+            &format!("sha256={}", &hex[..hex.len() - 2]),
+            &format!("sha256={}zz", &hex[..hex.len() - 2]),
+            "sha256=é",
+        ] {
+            assert!(!verify(SECRET, PAYLOAD, malformed), "accepted {malformed:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn deliver_retries_until_received() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let event = Event {
+            event: EventKind::BannerChanged,
+            guild_id: "1".to_string(),
+            channel_id: "2".to_string(),
+            target: "banner".to_string(),
+            url: None,
+            error: None,
+            timestamp: 0,
+        };
+        let payload = serde_json::to_vec(&event).unwrap();
+
+        let delivery = tokio::spawn(deliver(
+            Client::new(),
+            url,
+            event.event,
+            payload.clone(),
+            sign(SECRET, &payload),
+            1,
+        ));
+
+        // hang up on the first attempt without answering
+        let (first, _) = listener.accept().await.unwrap();
+        drop(first);
+
+        let (second, _) = listener.accept().await.unwrap();
+        let received = receive(second, SECRET).await.unwrap();
+        delivery.await.unwrap();
+
+        assert!(received.signature_valid);
+        assert_eq!(received.request_line, "POST /webhook HTTP/1.1");
+        assert_eq!(received.event_header, "banner_changed");
+        assert_eq!(received.body, payload);
+        assert_eq!(received.event().unwrap().event, EventKind::BannerChanged);
+    }
+
+    #[tokio::test]
+    async fn receive_rejects_wrong_signature() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let signature = sign("not the secret", PAYLOAD);
+        let request = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\nX-Signature-256: {signature}\r\n\r\n",
+            PAYLOAD.len()
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+        client.write_all(PAYLOAD).await.unwrap();
+
+        let (server, _) = listener.accept().await.unwrap();
+        let received = receive(server, SECRET).await.unwrap();
+        assert!(!received.signature_valid);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn receive_refuses_huge_bodies() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let request = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAXIMUM_BODY_SIZE + 1
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let (server, _) = listener.accept().await.unwrap();
+        assert!(receive(server, SECRET).await.is_err());
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"), "{response}");
+    }
+}
@@ -0,0 +1,103 @@
+//! A minimal webhook receiver
+//!
+//! Good enough to try out webhooks without a real dashboard, see the `webhook-receiver` util command.
+//! It reads a single http request per connection and doesn't support anything fancy like chunked bodies.
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use super::{EVENT_HEADER, Event, SIGNATURE_HEADER, verify};
+
+/// Bodies bigger than this are refused. Events are a few hundred bytes
+pub const MAXIMUM_BODY_SIZE: usize = 64 * 1024;
+
+/// A request the receiver got
+#[derive(Debug)]
+pub struct Delivery {
+    /// Like `POST /webhook HTTP/1.1`
+    pub request_line: String,
+    /// Value of the [EVENT_HEADER] header
+    pub event_header: String,
+    pub body: Vec<u8>,
+    /// Does the [SIGNATURE_HEADER] header match the body?
+    pub signature_valid: bool,
+}
+
+impl Delivery {
+    /// The body as an event
+    pub fn event(&self) -> Result<Event, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// Read a single http request, answer 200 if the signature is valid and 401 otherwise
+pub async fn receive(stream: TcpStream, secret: &str) -> std::io::Result<Delivery> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    let mut content_length = 0;
+    let mut signature = String::new();
+    let mut event_header = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().unwrap_or_default();
+        } else if name.eq_ignore_ascii_case(SIGNATURE_HEADER) {
+            signature = value.to_owned();
+        } else if name.eq_ignore_ascii_case(EVENT_HEADER) {
+            event_header = value.to_owned();
+        }
+    }
+
+    // the header is not to be trusted with how much memory to allocate
+    if content_length > MAXIMUM_BODY_SIZE {
+        respond(reader, "413 Content Too Large").await?;
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("body of {content_length} bytes is too big"),
+        ));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let signature_valid = verify(secret, &body, &signature);
+    respond(
+        reader,
+        if signature_valid {
+            "200 OK"
+        } else {
+            "401 Unauthorized"
+        },
+    )
+    .await?;
+
+    Ok(Delivery {
+        request_line: request_line.trim_end().to_owned(),
+        event_header,
+        body,
+        signature_valid,
+    })
+}
+
+async fn respond(reader: BufReader<TcpStream>, status: &str) -> std::io::Result<()> {
+    reader
+        .into_inner()
+        .write_all(format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").as_bytes())
+        .await
+}